@@ -0,0 +1,771 @@
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use alloy_primitives::{keccak256, B256};
+use anyhow::{Context, Result};
+use fckuipaid_verifier::unix_timestamp;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{de::DeserializeOwned, Serialize};
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::types::{AttemptInfo, GenerateProofResponse, JobStatus, OfferInfo, ProofJobResponse};
+
+/// Persisted record of an asynchronous proof generation job.
+///
+/// The guest stdin is stored alongside until the job finishes, so that a job
+/// interrupted by a restart after its market request was submitted can be
+/// resubmitted should that request expire. The market request publishes the same input.
+#[derive(Debug, Clone)]
+pub struct ProofJob {
+    pub id: String,
+    pub status: JobStatus,
+    /// Boundless request id of the latest attempt, as hex string
+    pub request_id: Option<String>,
+    /// Timestamp at which the latest market request expires
+    pub expires_at: Option<u64>,
+    pub offer: Option<OfferInfo>,
+    pub attempts: Vec<AttemptInfo>,
+    pub result: Option<GenerateProofResponse>,
+    pub error: Option<String>,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+impl From<ProofJob> for ProofJobResponse {
+    fn from(job: ProofJob) -> Self {
+        Self {
+            job_id: job.id,
+            status: job.status,
+            request_id: job.request_id,
+            offer: job.offer,
+            attempts: job.attempts,
+            result: job.result,
+            error: job.error,
+            created_at: job.created_at,
+            updated_at: job.updated_at,
+        }
+    }
+}
+
+/// Schema migrations, applied in order and tracked in `PRAGMA user_version`
+const MIGRATIONS: &[&str] = &[
+    "
+CREATE TABLE IF NOT EXISTS proof_jobs (
+    id          TEXT PRIMARY KEY,
+    status      TEXT NOT NULL,
+    request_id  TEXT,
+    expires_at  INTEGER,
+    offer       TEXT,
+    attempts    TEXT NOT NULL,
+    result      TEXT,
+    error       TEXT,
+    created_at  INTEGER NOT NULL,
+    updated_at  INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS proof_jobs_status ON proof_jobs (status);
+",
+    "
+ALTER TABLE proof_jobs ADD COLUMN idempotency_key TEXT;
+ALTER TABLE proof_jobs ADD COLUMN request_hash TEXT;
+CREATE INDEX proof_jobs_idempotency_key ON proof_jobs (idempotency_key, created_at);
+CREATE TABLE job_store_meta (
+    name   TEXT PRIMARY KEY,
+    value  BLOB NOT NULL
+);
+",
+    "
+ALTER TABLE proof_jobs ADD COLUMN input BLOB;
+",
+];
+
+const COLUMNS: &str =
+    "id, status, request_id, expires_at, offer, attempts, result, error, created_at, updated_at";
+
+/// Idempotency key of a proof request, with the hash of the request it was used for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdempotencyKey {
+    key: String,
+    request_hash: String,
+}
+
+/// Outcome of creating a job under an idempotency key
+#[derive(Debug)]
+pub enum Idempotent {
+    /// A new job was created
+    Created(ProofJob),
+    /// An in-flight or recently fulfilled job for the same request
+    Existing(ProofJob),
+    /// The key was used for a different request
+    Conflict,
+}
+
+/// Store of proof jobs shared between handlers and workers, persisted to SQLite
+/// so jobs survive a restart of the backend
+#[derive(Clone)]
+pub struct JobStore {
+    conn: Arc<Mutex<Connection>>,
+    /// Secret mixed into idempotency keys, so they do not reveal the user address
+    key_salt: B256,
+}
+
+impl JobStore {
+    /// Open (or create) the job database at `path`
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        let conn = Connection::open(path)
+            .with_context(|| format!("Failed to open job database {}", path.display()))?;
+        migrate(&conn).context("Failed to migrate job database")?;
+
+        conn.execute(
+            "INSERT OR IGNORE INTO job_store_meta (name, value) VALUES ('key_salt', ?1)",
+            params![B256::random().as_slice()],
+        )?;
+        let key_salt: Vec<u8> = conn.query_row(
+            "SELECT value FROM job_store_meta WHERE name = 'key_salt'",
+            [],
+            |row| row.get(0),
+        )?;
+
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+            key_salt: B256::try_from(key_salt.as_slice()).context("Invalid key salt")?,
+        })
+    }
+
+    /// Idempotency key for a request, identified by `request`. The key supplied by
+    /// the client is used if there is one, otherwise the key is derived from the request.
+    pub fn idempotency_key(&self, client_key: Option<&str>, request: &[u8]) -> IdempotencyKey {
+        let request_hash = keccak256([self.key_salt.as_slice(), request].concat());
+        let key = match client_key {
+            Some(client_key) => {
+                keccak256([self.key_salt.as_slice(), b"client:", client_key.as_bytes()].concat())
+            }
+            None => request_hash,
+        };
+        IdempotencyKey {
+            key: key.to_string(),
+            request_hash: request_hash.to_string(),
+        }
+    }
+
+    /// Look up a job that can be reused for `key`: one still in flight, or one
+    /// fulfilled less than `reuse_ttl` ago
+    pub async fn find(
+        &self,
+        key: &IdempotencyKey,
+        reuse_ttl: Duration,
+    ) -> Result<Option<Idempotent>> {
+        let key = key.clone();
+        self.with_conn(move |conn| find_reusable(conn, &key, reuse_ttl))
+            .await
+    }
+
+    /// Register a new job in the preflighting state, unless a reusable job exists
+    /// for the idempotency key
+    pub async fn create(
+        &self,
+        key: Option<&IdempotencyKey>,
+        reuse_ttl: Duration,
+    ) -> Result<Idempotent> {
+        let key = key.cloned();
+        self.with_conn(move |conn| create_job(conn, key.as_ref(), reuse_ttl))
+            .await
+    }
+
+    /// Look up a job by id
+    pub async fn get(&self, id: &str) -> Result<Option<ProofJob>> {
+        let id = id.to_string();
+        self.with_conn(move |conn| {
+            conn.query_row(
+                &format!("SELECT {} FROM proof_jobs WHERE id = ?1", COLUMNS),
+                params![id],
+                read_job,
+            )
+            .optional()
+            .context("Failed to read job")
+        })
+        .await
+    }
+
+    /// Guest stdin of an unfinished job
+    pub async fn input(&self, id: &str) -> Result<Option<Vec<u8>>> {
+        let id = id.to_string();
+        self.with_conn(move |conn| {
+            conn.query_row(
+                "SELECT input FROM proof_jobs WHERE id = ?1",
+                params![id],
+                |row| row.get::<_, Option<Vec<u8>>>(0),
+            )
+            .optional()
+            .map(Option::flatten)
+            .context("Failed to read job input")
+        })
+        .await
+    }
+
+    /// Jobs that have not reached a final state, oldest first
+    pub async fn unfinished(&self) -> Result<Vec<ProofJob>> {
+        self.with_conn(|conn| {
+            let mut statement = conn.prepare(&format!(
+                "SELECT {} FROM proof_jobs WHERE status NOT IN (?1, ?2, ?3) ORDER BY created_at",
+                COLUMNS
+            ))?;
+            let jobs = statement
+                .query_map(
+                    params![
+                        to_json(&JobStatus::Fulfilled)?,
+                        to_json(&JobStatus::Expired)?,
+                        to_json(&JobStatus::Failed)?
+                    ],
+                    read_job,
+                )?
+                .collect::<rusqlite::Result<Vec<_>>>()
+                .context("Failed to read unfinished jobs")?;
+            Ok(jobs)
+        })
+        .await
+    }
+
+    /// Handle used by a worker to report progress on a job
+    pub fn handle(&self, id: &str) -> JobHandle {
+        JobHandle {
+            store: self.clone(),
+            id: id.to_string(),
+        }
+    }
+
+    /// Run `f` on the connection on the blocking thread pool, as SQLite calls
+    /// block and would stall the async workers
+    async fn with_conn<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut Connection) -> Result<T> + Send + 'static,
+    ) -> Result<T> {
+        let conn = self.conn.clone();
+        tokio::task::spawn_blocking(move || f(&mut conn.lock().unwrap()))
+            .await
+            .context("Job store task panicked")?
+    }
+
+    /// Apply `f` to a job and write it back. Failures are logged, as workers
+    /// cannot do anything about them.
+    async fn update(&self, id: &str, f: impl FnOnce(&mut ProofJob) + Send + 'static) {
+        let job_id = id.to_string();
+        if let Err(e) = self
+            .with_conn(move |conn| update_job(conn, &job_id, f))
+            .await
+        {
+            error!("❌ Failed to update job {}: {:#}", id, e);
+        }
+    }
+
+    /// Store or clear the guest stdin of a job. Failures are logged like updates.
+    async fn set_input(&self, id: &str, input: Option<Vec<u8>>) {
+        let job_id = id.to_string();
+        if let Err(e) = self
+            .with_conn(move |conn| {
+                conn.execute(
+                    "UPDATE proof_jobs SET input = ?2 WHERE id = ?1",
+                    params![job_id, input],
+                )?;
+                Ok(())
+            })
+            .await
+        {
+            error!("❌ Failed to update input of job {}: {:#}", id, e);
+        }
+    }
+}
+
+/// Register a new job in the preflighting state, unless a reusable job exists
+/// for the idempotency key
+fn create_job(
+    conn: &Connection,
+    key: Option<&IdempotencyKey>,
+    reuse_ttl: Duration,
+) -> Result<Idempotent> {
+    if let Some(existing) = key
+        .map(|key| find_reusable(conn, key, reuse_ttl))
+        .transpose()?
+        .flatten()
+    {
+        return Ok(existing);
+    }
+
+    let now = unix_timestamp();
+    let job = ProofJob {
+        id: Uuid::new_v4().to_string(),
+        status: JobStatus::Preflighting,
+        request_id: None,
+        expires_at: None,
+        offer: None,
+        attempts: Vec::new(),
+        result: None,
+        error: None,
+        created_at: now,
+        updated_at: now,
+    };
+    conn.execute(
+        &format!(
+            "INSERT INTO proof_jobs ({}, idempotency_key, request_hash)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            COLUMNS
+        ),
+        params![
+            job.id,
+            to_json(&job.status)?,
+            job.request_id,
+            job.expires_at,
+            job.offer.as_ref().map(to_json).transpose()?,
+            to_json(&job.attempts)?,
+            job.result.as_ref().map(to_json).transpose()?,
+            job.error,
+            job.created_at,
+            job.updated_at,
+            key.map(|key| &key.key),
+            key.map(|key| &key.request_hash),
+        ],
+    )
+    .context("Failed to insert job")?;
+    Ok(Idempotent::Created(job))
+}
+
+/// Apply `f` to a job and write it back
+fn update_job(conn: &mut Connection, id: &str, f: impl FnOnce(&mut ProofJob)) -> Result<()> {
+    let tx = conn.transaction()?;
+    let Some(mut job) = tx
+        .query_row(
+            &format!("SELECT {} FROM proof_jobs WHERE id = ?1", COLUMNS),
+            params![id],
+            read_job,
+        )
+        .optional()?
+    else {
+        return Ok(());
+    };
+
+    f(&mut job);
+    job.updated_at = unix_timestamp();
+
+    tx.execute(
+        "UPDATE proof_jobs SET status = ?2, request_id = ?3, expires_at = ?4, offer = ?5,
+                attempts = ?6, result = ?7, error = ?8, updated_at = ?9
+             WHERE id = ?1",
+        params![
+            job.id,
+            to_json(&job.status)?,
+            job.request_id,
+            job.expires_at,
+            job.offer.as_ref().map(to_json).transpose()?,
+            to_json(&job.attempts)?,
+            job.result.as_ref().map(to_json).transpose()?,
+            job.error,
+            job.updated_at,
+        ],
+    )?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// Bring the database schema up to date
+fn migrate(conn: &Connection) -> Result<()> {
+    let version: usize = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        conn.execute_batch(migration)?;
+        conn.pragma_update(None, "user_version", index + 1)?;
+    }
+    Ok(())
+}
+
+/// Newest job with the idempotency key, if it can be reused. Jobs that failed or
+/// expired, and proofs older than `reuse_ttl`, are not reused.
+fn find_reusable(
+    conn: &Connection,
+    key: &IdempotencyKey,
+    reuse_ttl: Duration,
+) -> Result<Option<Idempotent>> {
+    let found = conn
+        .query_row(
+            &format!(
+                "SELECT request_hash, {} FROM proof_jobs WHERE idempotency_key = ?1
+                 ORDER BY created_at DESC LIMIT 1",
+                COLUMNS
+            ),
+            params![key.key],
+            |row| Ok((row.get::<_, Option<String>>(0)?, read_job_at(row, 1)?)),
+        )
+        .optional()
+        .context("Failed to look up idempotency key")?;
+
+    let Some((request_hash, job)) = found else {
+        return Ok(None);
+    };
+    let reusable = match job.status {
+        JobStatus::Failed | JobStatus::Expired => false,
+        JobStatus::Fulfilled => job.updated_at + reuse_ttl.as_secs() > unix_timestamp(),
+        _ => true,
+    };
+    if !reusable {
+        return Ok(None);
+    }
+    if request_hash.as_deref() != Some(key.request_hash.as_str()) {
+        return Ok(Some(Idempotent::Conflict));
+    }
+    Ok(Some(Idempotent::Existing(job)))
+}
+
+fn to_json<T: Serialize>(value: &T) -> Result<String> {
+    serde_json::to_string(value).context("Failed to serialize job field")
+}
+
+fn from_json<T: DeserializeOwned>(index: usize, value: &str) -> rusqlite::Result<T> {
+    serde_json::from_str(value).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(index, rusqlite::types::Type::Text, Box::new(e))
+    })
+}
+
+fn read_job(row: &Row<'_>) -> rusqlite::Result<ProofJob> {
+    read_job_at(row, 0)
+}
+
+/// Read the job [`COLUMNS`], starting at column `first`
+fn read_job_at(row: &Row<'_>, first: usize) -> rusqlite::Result<ProofJob> {
+    let json_column = |index: usize| -> rusqlite::Result<Option<String>> { row.get(first + index) };
+    Ok(ProofJob {
+        id: row.get(first)?,
+        status: from_json(first + 1, &row.get::<_, String>(first + 1)?)?,
+        request_id: row.get(first + 2)?,
+        expires_at: row.get(first + 3)?,
+        offer: json_column(4)?
+            .map(|offer| from_json(first + 4, &offer))
+            .transpose()?,
+        attempts: from_json(first + 5, &row.get::<_, String>(first + 5)?)?,
+        result: json_column(6)?
+            .map(|result| from_json(first + 6, &result))
+            .transpose()?,
+        error: row.get(first + 7)?,
+        created_at: row.get(first + 8)?,
+        updated_at: row.get(first + 9)?,
+    })
+}
+
+/// Progress reporter for a single job
+#[derive(Clone)]
+pub struct JobHandle {
+    store: JobStore,
+    id: String,
+}
+
+impl JobHandle {
+    /// Move the job to a new non-final state
+    pub async fn set_status(&self, status: JobStatus) {
+        info!("📌 Job {} -> {:?}", self.id, status);
+        self.store
+            .update(&self.id, move |job| job.status = status)
+            .await;
+    }
+
+    /// Record the offer the Boundless request is submitted with
+    pub async fn set_offer(&self, offer: OfferInfo) {
+        info!(
+            "📌 Job {} offered {}-{} wei for {} cycles",
+            self.id, offer.min_price, offer.max_price, offer.cycles
+        );
+        self.store
+            .update(&self.id, move |job| job.offer = Some(offer))
+            .await;
+    }
+
+    /// Record the Boundless request id and expiry of a new attempt and mark the
+    /// job as submitted
+    pub async fn submitted(&self, request_id: String, expires_at: u64) {
+        info!("📌 Job {} submitted as request {}", self.id, request_id);
+        self.store
+            .update(&self.id, move |job| {
+                job.status = JobStatus::Submitted;
+                job.request_id = Some(request_id.clone());
+                job.expires_at = Some(expires_at);
+                job.attempts.push(AttemptInfo {
+                    prover: "boundless".to_string(),
+                    request_id: Some(request_id),
+                    offer: job.offer.clone(),
+                    error: None,
+                });
+            })
+            .await;
+    }
+
+    /// Record an attempt with another prover backend, e.g. a local fallback
+    pub async fn attempt_started(&self, prover: &str) {
+        info!("📌 Job {} falling back to the {} prover", self.id, prover);
+        let prover = prover.to_string();
+        self.store
+            .update(&self.id, move |job| {
+                job.attempts.push(AttemptInfo {
+                    prover,
+                    request_id: None,
+                    offer: None,
+                    error: None,
+                });
+            })
+            .await;
+    }
+
+    /// Record why the latest attempt failed, before the job is retried. Attempts
+    /// that failed before they were submitted are recorded without request id.
+    pub async fn attempt_failed(&self, prover: &str, error: String) {
+        let prover = prover.to_string();
+        self.store
+            .update(&self.id, move |job| match job.attempts.last_mut() {
+                Some(attempt) if attempt.error.is_none() => attempt.error = Some(error),
+                _ => job.attempts.push(AttemptInfo {
+                    prover,
+                    request_id: None,
+                    offer: job.offer.clone(),
+                    error: Some(error),
+                }),
+            })
+            .await;
+    }
+
+    /// Keep the guest stdin until the job finishes, to resubmit it after a restart
+    pub async fn set_input(&self, input: Vec<u8>) {
+        self.store.set_input(&self.id, Some(input)).await;
+    }
+
+    /// Guest stdin of the job, unless it finished
+    pub async fn input(&self) -> Result<Option<Vec<u8>>> {
+        self.store.input(&self.id).await
+    }
+
+    /// Store the generated proof and mark the job as fulfilled
+    pub async fn complete(&self, result: GenerateProofResponse) {
+        info!("📌 Job {} fulfilled", self.id);
+        self.store
+            .update(&self.id, move |job| {
+                job.status = JobStatus::Fulfilled;
+                job.result = Some(result);
+            })
+            .await;
+        self.store.set_input(&self.id, None).await;
+    }
+
+    /// Record a failure. Jobs already marked as expired keep that state.
+    pub async fn fail(&self, error: String) {
+        info!("📌 Job {} failed: {}", self.id, error);
+        self.store
+            .update(&self.id, move |job| {
+                if job.status != JobStatus::Expired {
+                    job.status = JobStatus::Failed;
+                }
+                job.error = Some(error);
+            })
+            .await;
+        self.store.set_input(&self.id, None).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    const REUSE_TTL: Duration = Duration::from_secs(3600);
+
+    fn database() -> PathBuf {
+        std::env::temp_dir()
+            .join(format!("fckuipaid-jobs-{}", Uuid::new_v4()))
+            .join("jobs.sqlite")
+    }
+
+    fn offer() -> OfferInfo {
+        OfferInfo {
+            cycles: 2_000_000,
+            cycles_measured: true,
+            min_price: "100".to_string(),
+            max_price: "1000".to_string(),
+            ramp_up_period: 60,
+            lock_timeout: 600,
+            timeout: 1200,
+            lock_stake: "5".to_string(),
+        }
+    }
+
+    fn proof() -> GenerateProofResponse {
+        GenerateProofResponse {
+            success: true,
+            message: "proven".to_string(),
+            proof_id: "dev_2026-10_1".to_string(),
+            journal: "00".to_string(),
+            seal: "11".to_string(),
+            commitment: "22".to_string(),
+            proof_token: "fkp1_token".to_string(),
+            timestamp: 1,
+        }
+    }
+
+    async fn create(store: &JobStore, key: &IdempotencyKey) -> ProofJob {
+        match store.create(Some(key), REUSE_TTL).await.unwrap() {
+            Idempotent::Created(job) => job,
+            other => panic!("expected a new job, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn persists_jobs_across_restarts() {
+        let path = database();
+        let store = JobStore::open(&path).unwrap();
+        let key = store.idempotency_key(None, b"request");
+        let job = create(&store, &key).await;
+        assert_eq!(job.status, JobStatus::Preflighting);
+
+        let handle = store.handle(&job.id);
+        handle.set_input(b"stdin".to_vec()).await;
+        handle.set_offer(offer()).await;
+        handle.submitted("abc".to_string(), 1_000).await;
+        drop(store);
+
+        let store = JobStore::open(&path).unwrap();
+        // The key salt is kept, so repeated requests still find the job
+        assert_eq!(store.idempotency_key(None, b"request"), key);
+        match store.find(&key, REUSE_TTL).await.unwrap() {
+            Some(Idempotent::Existing(existing)) => assert_eq!(existing.id, job.id),
+            other => panic!("expected the existing job, got {:?}", other),
+        }
+
+        let job = store.get(&job.id).await.unwrap().unwrap();
+        assert_eq!(job.status, JobStatus::Submitted);
+        assert_eq!(job.request_id.as_deref(), Some("abc"));
+        assert_eq!(job.expires_at, Some(1_000));
+        assert_eq!(job.offer.unwrap().max_price, "1000");
+        assert_eq!(job.attempts.len(), 1);
+        assert_eq!(job.attempts[0].request_id.as_deref(), Some("abc"));
+
+        let unfinished = store.unfinished().await.unwrap();
+        assert_eq!(unfinished.len(), 1);
+        assert_eq!(unfinished[0].id, job.id);
+        assert_eq!(
+            store.input(&job.id).await.unwrap().as_deref(),
+            Some(&b"stdin"[..])
+        );
+
+        let handle = store.handle(&job.id);
+        handle.complete(proof()).await;
+        assert!(store.unfinished().await.unwrap().is_empty());
+        assert!(handle.input().await.unwrap().is_none());
+        let job = store.get(&job.id).await.unwrap().unwrap();
+        assert_eq!(job.status, JobStatus::Fulfilled);
+        assert_eq!(job.result.unwrap().proof_token, "fkp1_token");
+    }
+
+    #[tokio::test]
+    async fn migrates_new_databases() {
+        let store = JobStore::open(&database()).unwrap();
+        let version: usize = store
+            .with_conn(|conn| Ok(conn.pragma_query_value(None, "user_version", |row| row.get(0))?))
+            .await
+            .unwrap();
+        assert_eq!(version, MIGRATIONS.len());
+        assert!(store.get("missing").await.unwrap().is_none());
+        assert!(store.input("missing").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn reuses_jobs_by_idempotency_key() {
+        let store = JobStore::open(&database()).unwrap();
+        let key = store.idempotency_key(None, b"request");
+        assert!(store.find(&key, REUSE_TTL).await.unwrap().is_none());
+        let job = create(&store, &key).await;
+
+        match store.create(Some(&key), REUSE_TTL).await.unwrap() {
+            Idempotent::Existing(existing) => assert_eq!(existing.id, job.id),
+            other => panic!("expected the existing job, got {:?}", other),
+        }
+        let other = store.idempotency_key(None, b"other request");
+        assert!(store.find(&other, REUSE_TTL).await.unwrap().is_none());
+
+        // A client key reused for a different request conflicts
+        let client_key = store.idempotency_key(Some("key"), b"request");
+        create(&store, &client_key).await;
+        let reused = store.idempotency_key(Some("key"), b"other request");
+        assert!(matches!(
+            store.find(&reused, REUSE_TTL).await.unwrap(),
+            Some(Idempotent::Conflict)
+        ));
+
+        // Jobs without a key are never reused
+        assert!(matches!(
+            store.create(None, REUSE_TTL).await.unwrap(),
+            Idempotent::Created(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn does_not_reuse_failed_or_stale_jobs() {
+        let store = JobStore::open(&database()).unwrap();
+
+        let key = store.idempotency_key(None, b"failed");
+        let job = create(&store, &key).await;
+        store.handle(&job.id).fail("no proof".to_string()).await;
+        assert!(store.find(&key, REUSE_TTL).await.unwrap().is_none());
+        assert_ne!(create(&store, &key).await.id, job.id);
+
+        let key = store.idempotency_key(None, b"fulfilled");
+        let job = create(&store, &key).await;
+        store.handle(&job.id).complete(proof()).await;
+        assert!(store.find(&key, REUSE_TTL).await.unwrap().is_some());
+        assert!(store.find(&key, Duration::ZERO).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn records_attempts() {
+        let store = JobStore::open(&database()).unwrap();
+        let key = store.idempotency_key(None, b"request");
+        let job = create(&store, &key).await;
+        let handle = store.handle(&job.id);
+
+        handle.set_input(b"stdin".to_vec()).await;
+        handle.set_offer(offer()).await;
+        handle.submitted("a".to_string(), 1_000).await;
+        handle.set_status(JobStatus::Expired).await;
+        handle
+            .attempt_failed("boundless", "expired".to_string())
+            .await;
+        handle
+            .attempt_failed("boundless", "submission failed".to_string())
+            .await;
+        handle.attempt_started("local").await;
+        handle.fail("no proof".to_string()).await;
+
+        let job = store.get(&job.id).await.unwrap().unwrap();
+        // A job that expired keeps that state when it fails
+        assert_eq!(job.status, JobStatus::Expired);
+        assert_eq!(job.error.as_deref(), Some("no proof"));
+        let attempts: Vec<_> = job
+            .attempts
+            .iter()
+            .map(|attempt| {
+                (
+                    attempt.prover.as_str(),
+                    attempt.request_id.as_deref(),
+                    attempt.error.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            attempts,
+            [
+                ("boundless", Some("a"), Some("expired")),
+                ("boundless", None, Some("submission failed")),
+                ("local", None, None),
+            ]
+        );
+        assert!(job.attempts[1].offer.is_some());
+        assert!(handle.input().await.unwrap().is_none());
+    }
+}
@@ -1,4 +1,11 @@
-use axum::{extract::State, http::StatusCode, response::Json, routing::post, Router};
+use axum::{
+    async_trait,
+    extract::{rejection::JsonRejection, FromRequest, Path, Request, State},
+    http::{HeaderMap, StatusCode},
+    response::Json,
+    routing::{get, post},
+    Router,
+};
 use dotenvy::dotenv;
 use std::sync::Arc;
 
@@ -7,24 +14,41 @@ use tracing::{error, info};
 use tracing_subscriber;
 
 // ZK and blockchain imports
-use alloy::sol;
-use alloy_primitives::Address;
+use alloy::{sol, sol_types::SolValue};
+use alloy_primitives::{Address, Signature, B256};
 use anyhow::{Context, Result};
-use bincode;
-use boundless_market::Client;
+use fckuipaid_billing::BillingPeriod;
+use fckuipaid_chains::PAYMENT_CHAIN;
+use fckuipaid_methods::PAYMENT_CHECK_ID;
+use fckuipaid_verifier::{
+    is_low_s, legacy_signing_hash, unix_timestamp, CachedHeaderSource, ChallengeRegistry,
+    CommitmentValidator, Journal, LoginAuthorization, NullifierPolicy, NullifierTracker,
+    ProofToken, RpcHeaderSource, Verifier, NULLIFIER_KEY_MESSAGE,
+};
 use hex;
 use risc0_steel::{
-    ethereum::{EthEvmEnv, ETH_SEPOLIA_CHAIN_SPEC},
+    ethereum::{EthEvmEnv, EthEvmInput},
     host::BlockNumberOrTag,
-    Commitment, Contract,
+    Contract, EvmBlockHeader,
 };
-use revm_primitives::hardfork::SpecId;
-use risc0_steel::config::ChainSpec;
-use serde::{Deserialize, Serialize};
-use std::time::Duration;
-use url::Url;
+use serde::Serialize;
+mod config;
+mod jobs;
+mod offer;
+mod program;
+mod prover;
+mod services;
+mod storage;
 mod types;
-use types::{GenerateProofRequest, GenerateProofResponse};
+use config::{ChainConfig, Config, ProverBackend};
+use jobs::{IdempotencyKey, Idempotent, JobHandle, JobStore, ProofJob};
+use prover::{prover_from_config, ExecutionReport, Prover, ProverOutput, SubmittedRequest};
+use services::ServiceRegistry;
+use storage::{InputStorageBackend, LocalInputStorage};
+use types::{
+    ChallengeResponse, DryRunResponse, ErrorResponse, GenerateProofRequest, GenerateProofResponse,
+    JobStatus, ProofJobResponse, ServiceInfo, VerifyProofRequest, VerifyProofResponse,
+};
 
 // Define the Solidity interface for payment verification
 sol! {
@@ -32,182 +56,444 @@ sol! {
     interface PaymentReceiver {
         function hasPaid(address userAddr, uint64 month) public view returns (bool);
     }
+}
+
+/// Parsed and validated inputs of a [`GenerateProofRequest`]
+#[derive(Debug, Clone)]
+pub struct ProofInputs {
+    /// User's Ethereum address
+    pub user_address: Address,
+    /// Address of the PaymentReceiver contract
+    pub payment_receiver: Address,
+    /// Billing period of the payment
+    pub period: BillingPeriod,
+    /// Raw 65-byte signature as read by the guest
+    pub signature: Vec<u8>,
+    /// Raw 65-byte signature of the nullifier key message
+    pub nullifier_key: Vec<u8>,
+    /// Service-issued login challenge, zero if none was given
+    pub challenge: B256,
+    /// Epoch the nullifier is derived for
+    pub epoch: u64,
+    /// Unix timestamp until which the login authorization is valid
+    pub expiry: u64,
+    /// The signature is over the fixed legacy message instead of an EIP-712 authorization
+    pub legacy_signature: bool,
+}
+
+impl ProofInputs {
+    /// Parse and validate the fields of an incoming request, using `default_epoch`
+    /// when the request does not name a nullifier epoch
+    pub fn from_request(
+        request: &GenerateProofRequest,
+        default_epoch: u64,
+        allow_legacy_signatures: bool,
+    ) -> Result<Self, String> {
+        let user_address = request
+            .user_address
+            .trim()
+            .parse::<Address>()
+            .map_err(|e| format!("Invalid user_address '{}': {}", request.user_address, e))?;
+        let payment_receiver = request
+            .payment_receiver
+            .trim()
+            .parse::<Address>()
+            .map_err(|e| {
+                format!(
+                    "Invalid payment_receiver '{}': {}",
+                    request.payment_receiver, e
+                )
+            })?;
+
+        let signature = parse_signature("signature", &request.signature)?;
+        let nullifier_key = parse_signature("nullifier_key", &request.nullifier_key)?;
+
+        let challenge = match &request.challenge {
+            Some(challenge) => challenge
+                .trim()
+                .parse::<B256>()
+                .map_err(|e| format!("Invalid challenge '{}': {}", challenge, e))?,
+            None => B256::ZERO,
+        };
+
+        let expiry = if request.legacy_signature {
+            if !allow_legacy_signatures {
+                return Err(
+                    "Legacy signatures are not accepted, sign a login authorization".to_string(),
+                );
+            }
+            0
+        } else {
+            let expiry = request
+                .expiry
+                .ok_or_else(|| "Missing expiry of the login authorization".to_string())?;
+            if expiry <= unix_timestamp() {
+                return Err(format!("Login authorization expired at {}", expiry));
+            }
+            expiry
+        };
 
-    /// Data committed to by the guest.
-    struct Journal {
-        Commitment commitment;
-        uint64 month;
+        Ok(Self {
+            user_address,
+            payment_receiver,
+            period: request.month,
+            signature,
+            nullifier_key,
+            challenge,
+            epoch: request.epoch.unwrap_or(default_epoch),
+            expiry,
+            legacy_signature: request.legacy_signature,
+        })
+    }
+}
+
+/// Parse a hex-encoded 65-byte signature field of a request
+fn parse_signature(field: &str, hex_signature: &str) -> Result<Vec<u8>, String> {
+    let signature = hex::decode(hex_signature.trim().trim_start_matches("0x"))
+        .map_err(|e| format!("Invalid {} hex: {}", field, e))?;
+    if signature.len() != 65 {
+        return Err(format!(
+            "Invalid {} length: expected 65 bytes, got {}",
+            field,
+            signature.len()
+        ));
     }
+    Signature::try_from(signature.as_slice()).map_err(|e| format!("Invalid {}: {}", field, e))?;
+    Ok(signature)
+}
+
+impl ProofInputs {
+    /// Identity of the request for idempotency: only requests for the same guest
+    /// input get the same proof, as every input field ends up in the journal or
+    /// decides whether the guest accepts it
+    fn idempotency_data(&self) -> Vec<u8> {
+        [
+            self.payment_receiver.as_slice(),
+            self.user_address.as_slice(),
+            &self.period.to_onchain().to_be_bytes(),
+            self.challenge.as_slice(),
+            &self.signature,
+            &self.nullifier_key,
+            &self.epoch.to_be_bytes(),
+            &self.expiry.to_be_bytes(),
+            &[u8::from(self.legacy_signature)],
+        ]
+        .concat()
+    }
+
+    /// Recover the signer of the login authorization (or of the legacy message),
+    /// the same way the guest does
+    fn recover_signer(&self, chain_id: u64) -> Option<Address> {
+        let signature = Signature::try_from(self.signature.as_slice()).ok()?;
+        // The guest rejects the malleable high-s twin of a signature
+        if !is_low_s(&signature) {
+            return None;
+        }
+        if self.legacy_signature {
+            signature
+                .recover_address_from_msg(legacy_signing_hash())
+                .ok()
+        } else {
+            let authorization = LoginAuthorization {
+                paymentReceiver: self.payment_receiver,
+                chainId: chain_id,
+                month: self.period.to_onchain(),
+                expiry: self.expiry,
+            };
+            signature
+                .recover_address_from_prehash(&authorization.signing_hash())
+                .ok()
+        }
+    }
+
+    /// Recover the signer of the nullifier key, the same way the guest does
+    fn recover_nullifier_key_signer(&self) -> Option<Address> {
+        let nullifier_key = Signature::try_from(self.nullifier_key.as_slice()).ok()?;
+        // A high-s twin would derive a second nullifier, the guest rejects it
+        if !is_low_s(&nullifier_key) {
+            return None;
+        }
+        nullifier_key
+            .recover_address_from_msg(NULLIFIER_KEY_MESSAGE)
+            .ok()
+    }
+}
+
+/// Reasons a request is rejected before anything is submitted for proving,
+/// because the guest would fail on it
+#[derive(Debug, thiserror::Error)]
+pub enum PreflightError {
+    #[error("billing period {0} is not current")]
+    PeriodNotCurrent(BillingPeriod),
+    #[error("nullifier epoch {epoch} is not the epoch {block_epoch} of the latest block")]
+    EpochMismatch { epoch: u64, block_epoch: u64 },
+    #[error("signature does not recover to {0}")]
+    BadSignature(Address),
+    #[error("nullifier key was not signed by {0}")]
+    BadNullifierKey(Address),
+    #[error("{user} has not paid {receiver} for {period}")]
+    NotPaid {
+        user: Address,
+        receiver: Address,
+        period: BillingPeriod,
+    },
+    #[error("failed to preflight payment check: {0:#}")]
+    Chain(#[from] anyhow::Error),
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct SubscriptionProof {
-    pub service_id: u32,
-    pub is_valid: bool,
-    pub payment_verified: bool,
-    pub timestamp: u64,
-    pub user_commitment: [u8; 32],
+impl PreflightError {
+    /// Machine-readable error code
+    pub fn reason(&self) -> &'static str {
+        match self {
+            Self::PeriodNotCurrent(_) => "PERIOD_NOT_CURRENT",
+            Self::EpochMismatch { .. } => "EPOCH_MISMATCH",
+            Self::BadSignature(_) => "BAD_SIGNATURE",
+            Self::BadNullifierKey(_) => "BAD_NULLIFIER_KEY",
+            Self::NotPaid { .. } => "NOT_PAID",
+            Self::Chain(_) => "PREFLIGHT_ERROR",
+        }
+    }
+
+    /// HTTP status the error is reported with
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Self::PeriodNotCurrent(_) | Self::EpochMismatch { .. } => StatusCode::BAD_REQUEST,
+            Self::BadSignature(_) | Self::BadNullifierKey(_) => StatusCode::UNAUTHORIZED,
+            Self::NotPaid { .. } => StatusCode::PAYMENT_REQUIRED,
+            Self::Chain(_) => StatusCode::BAD_GATEWAY,
+        }
+    }
 }
 
 /// Generate ZK proofs using RISC Zero Steel
 pub struct ProofGenerator {
-    /// Service name for logging
-    service_name: String,
-    /// Arbitrum Sepolia RPC URL for EvmEnv
-    arb_rpc_url: Url,
-    /// Ethereum Sepolia RPC URL for Boundless Market
-    sepolia_rpc_url: Url,
+    /// Chain the PaymentReceiver contracts are deployed on
+    chain: ChainConfig,
+    /// How long after its end a billing period still grants access
+    grace_period: std::time::Duration,
+    /// Nullifier epochs, which the guest binds to the execution block
+    nullifier_policy: NullifierPolicy,
+    /// Backend producing the proof from the prepared guest input
+    prover: Arc<dyn Prover>,
+}
+
+/// Append a value to the guest stdin in the format expected by `env::read()`
+fn write_input<T: Serialize>(input: &mut Vec<u8>, value: &T) -> Result<()> {
+    let words = risc0_zkvm::serde::to_vec(value)?;
+    input.extend_from_slice(bytemuck::cast_slice(&words));
+    Ok(())
 }
 
 impl ProofGenerator {
-    /// Generate a ZK proof for subscription verification using Steel
-    pub async fn generate_proof(
+    /// Run the checks the guest asserts on the host, and preflight the payment
+    /// check to prepare the guest's EVM input, returned with the timestamp of its block
+    pub async fn preflight(
         &self,
-        request: &GenerateProofRequest,
-    ) -> Result<GenerateProofResponse> {
-        info!("🔮 Generating ZK proof for subscription verification using Steel");
+        inputs: &ProofInputs,
+    ) -> Result<(EthEvmInput, u64), PreflightError> {
         info!(
-            "📋 Request: user_address={}, payment_receiver={}, month={}",
-            request.user_address, request.payment_receiver, request.month
+            "📋 Request: user_address={}, payment_receiver={}, period={}",
+            inputs.user_address, inputs.payment_receiver, inputs.period
         );
 
-        // Parse request parameters
-        let user_address = "0x170f6F7b0925CF1447BAAF25a5AE61253EF31c1B"
-            .parse::<Address>()
-            .context("Invalid user address format")?;
-        let payment_receiver = "0x6fEDEb0B4942A8b438AFE68ba7c8Af4637c41903"
-            .parse::<Address>()
-            .context("Invalid payment receiver address format")?;
-        let month = 072025;
-        let signature_bytes = hex::decode(&"0x184bdcdfb9db09b6f55c7bcdd3907e2a8555d599b63289fe1fe014864fe605bf01a0f53bcbc539f2070480162f745c847e77e4bcd8821a39ba416945c1b1666c1b".trim_start_matches("0x"))
-            .context("Invalid signature format")?;
+        self.authorize(inputs)?;
 
-        info!("🔧 Setting up Steel environment for Ethereum Sepolia");
+        let (evm_input, returns, block_timestamp) = self.preflight_call(inputs).await?;
+        // The guest only derives nullifiers for the epoch of the execution block
+        let block_epoch = self.nullifier_policy.epoch_at(block_timestamp);
+        if inputs.epoch != block_epoch {
+            return Err(PreflightError::EpochMismatch {
+                epoch: inputs.epoch,
+                block_epoch,
+            });
+        }
+        if !returns {
+            return Err(PreflightError::NotPaid {
+                user: inputs.user_address,
+                receiver: inputs.payment_receiver,
+                period: inputs.period,
+            });
+        }
+
+        Ok((evm_input, block_timestamp))
+    }
+
+    /// Checks of the guest that need no chain access: the period is current and
+    /// the signature was made by the user
+    pub fn authorize(&self, inputs: &ProofInputs) -> Result<(), PreflightError> {
+        // A proof for a past or future period would be rejected when verified
+        if !inputs.period.is_current(self.grace_period) {
+            return Err(PreflightError::PeriodNotCurrent(inputs.period));
+        }
+
+        if inputs.recover_signer(self.chain.chain_id()) != Some(inputs.user_address) {
+            return Err(PreflightError::BadSignature(inputs.user_address));
+        }
+        if inputs.recover_nullifier_key_signer() != Some(inputs.user_address) {
+            return Err(PreflightError::BadNullifierKey(inputs.user_address));
+        }
+        Ok(())
+    }
+
+    /// Preflight the `hasPaid` call to prepare the guest's EVM input, returning it
+    /// together with the result of the call and the timestamp of the block
+    async fn preflight_call(&self, inputs: &ProofInputs) -> Result<(EthEvmInput, bool, u64)> {
+        info!("🔧 Setting up Steel environment for Arbitrum Sepolia");
 
         // Create Steel EVM environment using the builder pattern from publisher.rs
         let builder = EthEvmEnv::builder()
-            .rpc(self.arb_rpc_url.clone())
+            .rpc(self.chain.rpc_url.expose().clone())
             .block_number_or_tag(BlockNumberOrTag::Latest);
 
-        // Create Arbitrum Sepolia chain spec
-        let arb_sepolia_chain_spec = ChainSpec::new_single(421614, SpecId::SHANGHAI);
+        let chain_spec = self.chain.name.chain_spec();
 
-        let mut env = builder.chain_spec(&arb_sepolia_chain_spec).build().await?;
-
-        info!(
-            "📞 Prepared EVM input for user {} to contract {} for month {}",
-            user_address, payment_receiver, month
-        );
+        let mut env = builder.chain_spec(&chain_spec).build().await?;
 
         // Prepare the hasPaid function call
         let call = PaymentReceiver::hasPaidCall {
-            userAddr: user_address,
-            month,
+            userAddr: inputs.user_address,
+            month: inputs.period.to_onchain(),
         };
 
         // Preflight the call to prepare the input that is required to execute the function in
         // the guest without RPC access. It also returns the result of the call.
-        let mut contract = Contract::preflight(payment_receiver, &mut env);
+        let mut contract = Contract::preflight(inputs.payment_receiver, &mut env);
         let returns = contract.call_builder(&call).call().await?;
 
         info!("📊 Contract call result: hasPaid = {}", returns);
 
+        // The guest checks the billing period and the nullifier epoch against this timestamp
+        let block_timestamp = env.header().timestamp();
+
         // Finally, construct the input from the environment.
         let evm_input = env.into_input().await?;
 
         info!("✅ Steel proof preparation complete");
 
-        // Download the guest program ELF from IPFS
-        // Using dweb.link IPFS gateway for better reliability
-        let program_url =
-            "https://dweb.link/ipfs/bafybeibrj22d3fthskfsokat6gklu77pjwynqn4aocgl7mwxq3ak2u3eje"
-                .to_string();
+        Ok((evm_input, returns, block_timestamp))
+    }
 
-        info!("📥 Using guest program from IPFS: {}", program_url);
+    /// Serialize the guest stdin for the given inputs and preflighted EVM input
+    fn guest_stdin(&self, inputs: &ProofInputs, evm_input: &EthEvmInput) -> Result<Vec<u8>> {
+        // The guest program calls env::read() 12 times sequentially to read:
+        // 1. EthEvmInput, 2. Address (payment_contract), 3. u64 (on-chain month count),
+        // 4. Address (user), 5. Vec<u8> (signature), 6. Vec<u8> (nullifier key),
+        // 7. B256 (challenge), 8. u64 (nullifier epoch), 9. u64 (epoch length secs),
+        // 10. u64 (authorization expiry), 11. bool (legacy signature), 12. u64 (grace period secs)
+        let mut input_bytes = Vec::new();
+        write_input(&mut input_bytes, evm_input).context("Failed to serialize EVM input")?;
+        write_input(&mut input_bytes, &inputs.payment_receiver)
+            .context("Failed to serialize payment_receiver")?;
+        write_input(&mut input_bytes, &inputs.period.to_onchain())
+            .context("Failed to serialize month")?;
+        write_input(&mut input_bytes, &inputs.user_address)
+            .context("Failed to serialize user_address")?;
+        write_input(&mut input_bytes, &inputs.signature)
+            .context("Failed to serialize signature_bytes")?;
+        write_input(&mut input_bytes, &inputs.nullifier_key)
+            .context("Failed to serialize nullifier_key")?;
+        write_input(&mut input_bytes, &inputs.challenge)
+            .context("Failed to serialize challenge")?;
+        write_input(&mut input_bytes, &inputs.epoch).context("Failed to serialize epoch")?;
+        let epoch_length = self.nullifier_policy.epoch_length.as_secs();
+        write_input(&mut input_bytes, &epoch_length).context("Failed to serialize epoch length")?;
+        write_input(&mut input_bytes, &inputs.expiry).context("Failed to serialize expiry")?;
+        write_input(&mut input_bytes, &inputs.legacy_signature)
+            .context("Failed to serialize legacy_signature")?;
+        write_input(&mut input_bytes, &self.grace_period.as_secs())
+            .context("Failed to serialize grace_period")?;
+        Ok(input_bytes)
+    }
 
-        // Get and parse private key from environment
-        let private_key_str =
-            std::env::var("PRIVATE_KEY").context("PRIVATE_KEY environment variable not set")?;
-        let private_key = private_key_str
-            .parse::<alloy::signers::local::PrivateKeySigner>()
-            .context("Failed to parse private key")?;
+    /// Execute the guest without proving. Unlike [`Self::preflight`] this does not
+    /// reject requests the guest would fail on, but reports which check panicked
+    /// and how many cycles the execution took.
+    pub async fn dry_run(&self, inputs: &ProofInputs) -> Result<ExecutionReport> {
+        info!(
+            "🧪 Dry run: user_address={}, payment_receiver={}, period={}",
+            inputs.user_address, inputs.payment_receiver, inputs.period
+        );
+        let (evm_input, _, _) = self.preflight_call(inputs).await?;
+        let stdin = self.guest_stdin(inputs, &evm_input)?;
+        let report = prover::execute(stdin).await?;
+        match &report.failure {
+            Some((failure, message)) => info!("🧪 Dry run failed: {} ({})", failure, message),
+            None => info!(
+                "🧪 Dry run succeeded in {} cycles ({} total, {} segments)",
+                report.user_cycles(),
+                report.total_cycles(),
+                report.segments.len()
+            ),
+        }
+        Ok(report)
+    }
 
-        // Parse program URL
-        let program_url = program_url
-            .parse::<Url>()
-            .context("Failed to parse program URL")?;
+    /// Generate a ZK proof for subscription verification from a preflighted EVM input
+    /// of the block at `block_timestamp`
+    pub async fn generate_proof(
+        &self,
+        inputs: &ProofInputs,
+        evm_input: EthEvmInput,
+        block_timestamp: u64,
+        job: &JobHandle,
+    ) -> Result<GenerateProofResponse> {
+        info!("🔮 Generating ZK proof for subscription verification using Steel");
 
         // Prepare input data for the guest program
-        // The guest program calls env::read() 5 times sequentially to read:
-        // 1. EthEvmInput, 2. Address (payment_contract), 3. u64 (month), 4. Address (user), 5. Vec<u8> (signature)
+        let input_bytes = self.guest_stdin(inputs, &evm_input)?;
+        job.set_input(input_bytes.clone()).await;
 
-        // Each env::read() expects a complete bincode-serialized object
-        // We need to write them sequentially to stdin
+        let valid_until = block_timestamp + self.chain.max_block_age_secs;
+        let output = self.prover.prove(input_bytes, valid_until, job).await?;
+        self.proof_response(output)
+    }
 
-        let mut input_bytes = Vec::new();
+    /// Resume a job whose market request was submitted before the backend restarted.
+    /// The prover waits for that request and continues with the remaining attempts.
+    pub async fn resume(
+        &self,
+        job: &ProofJob,
+        handle: &JobHandle,
+    ) -> Result<GenerateProofResponse> {
+        let (Some(request_id), Some(expires_at)) = (&job.request_id, job.expires_at) else {
+            anyhow::bail!("interrupted by a backend restart before it was submitted");
+        };
+        if !matches!(job.status, JobStatus::Submitted | JobStatus::Locked) {
+            anyhow::bail!("interrupted by a backend restart while {:?}", job.status);
+        }
+        let Some(stdin) = handle.input().await? else {
+            anyhow::bail!("interrupted by a backend restart, its guest input was not kept");
+        };
+        let request = SubmittedRequest {
+            request_id: request_id.clone(),
+            expires_at,
+            attempt: job
+                .attempts
+                .iter()
+                .filter(|attempt| attempt.request_id.is_some())
+                .count()
+                .try_into()?,
+            // The job was created right after the preflight of the latest block
+            valid_until: job.created_at + self.chain.max_block_age_secs,
+        };
+        let output = self.prover.resume(stdin, request, handle).await?;
+        self.proof_response(output)
+    }
 
-        // 1. EthEvmInput - Use the real Steel-generated EVM input
-        let evm_input_serialized =
-            bincode::serialize(&evm_input).context("Failed to serialize EVM input")?;
-        input_bytes.extend(evm_input_serialized);
+    /// Build the response from the prover output. Only data from the journal is
+    /// used, as the response is persisted with the job.
+    fn proof_response(&self, output: ProverOutput) -> Result<GenerateProofResponse> {
+        let current_timestamp = unix_timestamp();
 
-        // 2. Payment contract address
-        let payment_contract_serialized = bincode::serialize(&payment_receiver)
-            .context("Failed to serialize payment_receiver")?;
-        input_bytes.extend(payment_contract_serialized);
-
-        // 3. Month
-        let month_serialized = bincode::serialize(&month).context("Failed to serialize month")?;
-        input_bytes.extend(month_serialized);
-
-        // 4. User address
-        let user_address_serialized =
-            bincode::serialize(&user_address).context("Failed to serialize user_address")?;
-        input_bytes.extend(user_address_serialized);
-
-        // 5. Signature bytes
-        let signature_serialized =
-            bincode::serialize(&signature_bytes).context("Failed to serialize signature_bytes")?;
-        input_bytes.extend(signature_serialized);
-
-        info!("🔮 Generating ZK proof with Boundless SDK");
-
-        // Create a Boundless client from the provided parameters
-        // Try without deployment configuration first to see if it's required
-        let client = Client::builder()
-            .with_rpc_url(self.sepolia_rpc_url.clone())
-            .with_private_key(private_key)
-            .build()
-            .await?;
-
-        let boundless_request = client
-            .new_request()
-            .with_program_url(program_url)?
-            .with_stdin(input_bytes.clone());
-
-        let (request_id, expires_at) = client.submit_offchain(boundless_request).await?;
-
-        info!("⏳ Waiting for request {:x} to be fulfilled", request_id);
-        let (_journal, seal) = client
-            .wait_for_request_fulfillment(
-                request_id,
-                Duration::from_secs(5), // check every 5 seconds
-                expires_at,
-            )
-            .await?;
-        info!("✅ Request {:x} fulfilled", request_id);
-
-        // Generate response data
-        let current_timestamp = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .context("Failed to get current timestamp")?
-            .as_secs();
-
-        let proof_id = format!("boundless_{}_{}", month, current_timestamp);
-        let journal_hex = hex::encode(&_journal);
-        let seal_hex = hex::encode(&seal);
-        let commitment_hex =
-            hex::encode(format!("{}:{}:{}", user_address, payment_receiver, month));
+        let journal =
+            Journal::abi_decode(&output.journal).context("Prover returned an invalid journal")?;
+        let period = BillingPeriod::from_onchain(journal.month)
+            .context("Prover returned an invalid billing period")?;
+        let proof_id = format!("{}_{}_{}", self.prover.name(), period, current_timestamp);
+        let journal_hex = hex::encode(&output.journal);
+        let seal_hex = hex::encode(&output.seal);
+        let commitment_hex = hex::encode(journal.commitment.abi_encode());
+        let proof_token = ProofToken::new(PAYMENT_CHECK_ID, output.seal, output.journal).encode();
         info!(
             "🎉 Steel ZK proof generated successfully with ID: {}",
             proof_id
@@ -216,87 +502,547 @@ impl ProofGenerator {
         Ok(GenerateProofResponse {
             success: true,
             message: format!(
-                "Boundless ZK proof generated for user {} payment to {} for month {}",
-                user_address, payment_receiver, month
+                "ZK proof generated with {} prover for payment to {} for {}",
+                self.prover.name(),
+                journal.paymentContract,
+                period
             ),
             proof_id,
             journal: journal_hex,
             seal: seal_hex,
             commitment: commitment_hex,
+            proof_token,
             timestamp: current_timestamp,
         })
     }
 
     /// Create a new ProofGenerator
-    pub async fn new(service_name: String) -> Result<Self> {
-        let arb_rpc_url = std::env::var("ARB_RPC_URL")
-            .context("ARB_RPC_URL environment variable not set")?;
-
-        let sepolia_rpc_url = std::env::var("SEPOLIA_RPC_URL")
-            .context("SEPOLIA_RPC_URL environment variable not set")?;
+    pub fn new(
+        chain: ChainConfig,
+        grace_period: std::time::Duration,
+        nullifier_policy: NullifierPolicy,
+        prover: Arc<dyn Prover>,
+    ) -> Self {
+        Self {
+            chain,
+            grace_period,
+            nullifier_policy,
+            prover,
+        }
+    }
 
-        Ok(Self {
-            service_name,
-            arb_rpc_url: Url::parse(&arb_rpc_url).context("Invalid RPC URL")?,
-            sepolia_rpc_url: Url::parse(&sepolia_rpc_url).context("Invalid RPC URL")?,
-        })
+    /// Chain ID of the PaymentReceiver contracts
+    pub fn chain_id(&self) -> u64 {
+        self.chain.chain_id()
     }
 
-    /// Download ELF binary from IPFS
-    async fn download_elf_from_ipfs(&self, ipfs_url: &str) -> Result<Vec<u8>> {
-        info!("📡 Downloading ELF from IPFS: {}", ipfs_url);
+    /// How long after its end a billing period still grants access
+    pub fn grace_period(&self) -> std::time::Duration {
+        self.grace_period
+    }
+}
 
-        let response = reqwest::get(ipfs_url)
-            .await
-            .context("Failed to make HTTP request to IPFS")?;
+/// Verify a proof produced by the payment check guest
+///
+/// Accepts either a proof token or a seal/journal pair, verifies it with the
+/// verifier crate, checks the Steel commitment against the Arbitrum chain and
+/// that the committed billing period matches the claimed one and is current.
+async fn verify_proof(state: &AppState, request: &VerifyProofRequest) -> VerifyProofResponse {
+    let verifier = &state.verifier;
+    let result = match (&request.proof_token, &request.proof, &request.commitment) {
+        (Some(token), _, _) => verifier.verify_token(token),
+        (None, Some(proof), Some(commitment)) => {
+            let seal = match hex::decode(proof.trim().trim_start_matches("0x")) {
+                Ok(seal) => seal,
+                Err(e) => {
+                    error!("❌ Invalid seal hex: {}", e);
+                    return VerifyProofResponse::denied("INVALID_SEAL_ENCODING");
+                }
+            };
+            let journal = match hex::decode(commitment.trim().trim_start_matches("0x")) {
+                Ok(journal) => journal,
+                Err(e) => {
+                    error!("❌ Invalid journal hex: {}", e);
+                    return VerifyProofResponse::denied("INVALID_JOURNAL_ENCODING");
+                }
+            };
+            verifier.verify_seal(&seal, &journal)
+        }
+        _ => return VerifyProofResponse::denied("MISSING_PROOF"),
+    };
 
-        if !response.status().is_success() {
-            return Err(anyhow::anyhow!(
-                "IPFS request failed with status: {}",
-                response.status()
-            ));
+    let payment = match result {
+        Ok(payment) => payment,
+        Err(e) => {
+            error!("❌ Proof verification failed: {}", e);
+            return VerifyProofResponse::denied(e.reason());
         }
+    };
 
-        let elf_bytes = response
-            .bytes()
-            .await
-            .context("Failed to read ELF bytes from IPFS response")?
-            .to_vec();
+    let payment_receiver = match request.payment_receiver.trim().parse::<Address>() {
+        Ok(address) => address,
+        Err(e) => {
+            error!("❌ Invalid payment_receiver: {}", e);
+            return VerifyProofResponse::denied("INVALID_PAYMENT_RECEIVER");
+        }
+    };
+    if payment.chain_id != state.proof_generator.chain_id()
+        || payment.payment_contract != payment_receiver
+    {
+        info!(
+            "🚫 Proof is for receiver {} on chain {}, expected {}",
+            payment.payment_contract, payment.chain_id, payment_receiver
+        );
+        return VerifyProofResponse::denied("WRONG_PAYMENT_RECEIVER");
+    }
+    let Some(service) = state
+        .services
+        .by_receiver(payment.chain_id, payment.payment_contract)
+    else {
+        info!(
+            "🚫 No service is registered for receiver {}",
+            payment.payment_contract
+        );
+        return VerifyProofResponse::denied("UNKNOWN_SERVICE");
+    };
 
+    if payment.period != request.month_year {
         info!(
-            "✅ Successfully downloaded {} bytes from IPFS",
-            elf_bytes.len()
+            "🚫 Proof is for {} but {} was claimed",
+            payment.period, request.month_year
         );
-        Ok(elf_bytes)
+        return VerifyProofResponse::denied("MONTH_MISMATCH");
+    }
+    if !payment
+        .period
+        .is_current(state.proof_generator.grace_period())
+    {
+        info!("🚫 Billing period {} is not current", payment.period);
+        return VerifyProofResponse::denied("PERIOD_NOT_CURRENT");
+    }
+
+    let header = match state
+        .commitment_validator
+        .validate(&payment.commitment)
+        .await
+    {
+        Ok(header) => header,
+        Err(e) => {
+            error!("❌ Steel commitment rejected: {}", e);
+            return VerifyProofResponse::denied(e.reason());
+        }
+    };
+
+    // The proof must be bound to the challenge issued for this login, which is
+    // redeemed so the same proof cannot be replayed
+    let challenge = match request.challenge.trim().parse::<B256>() {
+        Ok(challenge) => challenge,
+        Err(e) => {
+            error!("❌ Invalid challenge: {}", e);
+            return VerifyProofResponse::denied("INVALID_CHALLENGE");
+        }
+    };
+    if let Err(e) = payment.check_challenge(challenge) {
+        error!("❌ {}", e);
+        return VerifyProofResponse::denied(e.reason());
+    }
+    if let Err(e) = state.challenges.consume(&challenge) {
+        error!("❌ {}", e);
+        return VerifyProofResponse::denied(e.reason());
+    }
+
+    // Limit how often one paid account can log in per epoch
+    match state
+        .nullifiers
+        .record(payment.nullifier, payment.epoch, payment.block_timestamp)
+    {
+        Ok(uses) => info!(
+            "🔑 Nullifier {} used {} time(s) in epoch {}",
+            payment.nullifier, uses, payment.epoch
+        ),
+        Err(e) => {
+            error!("❌ {}", e);
+            return VerifyProofResponse::denied(e.reason());
+        }
     }
+
+    info!(
+        "✅ Proof verified for {} against block {} ({})",
+        payment.period, header.number, header.hash
+    );
+    VerifyProofResponse::granted(&service.name, payment.period)
 }
 
 /// Application state
 #[derive(Clone)]
 struct AppState {
     proof_generator: Arc<ProofGenerator>,
+    jobs: JobStore,
+    /// How long a fulfilled job is returned for repeated requests
+    job_reuse_ttl: std::time::Duration,
+    verifier: Verifier,
+    commitment_validator: CommitmentValidator,
+    challenges: Arc<ChallengeRegistry>,
+    nullifiers: Arc<NullifierTracker>,
+    services: Arc<ServiceRegistry>,
+    /// Set when guest inputs are served by this backend
+    local_inputs: Option<Arc<LocalInputStorage>>,
+}
+
+/// JSON request body. Bodies that cannot be deserialized, e.g. because of a
+/// malformed billing period, are rejected as validation errors.
+struct ValidatedJson<T>(T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for ValidatedJson<T>
+where
+    Json<T>: FromRequest<S, Rejection = JsonRejection>,
+    S: Send + Sync,
+{
+    type Rejection = (StatusCode, Json<ErrorResponse>);
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(value) = Json::<T>::from_request(req, state)
+            .await
+            .map_err(|rejection| {
+                error!("❌ Invalid request body: {}", rejection.body_text());
+                (
+                    StatusCode::BAD_REQUEST,
+                    Json(ErrorResponse::validation_error(rejection.body_text())),
+                )
+            })?;
+        Ok(Self(value))
+    }
 }
 
 /// 🔮 Generate ZK Proof Endpoint
 ///
 /// POST /generate-proof
 ///
-/// Creates a zero-knowledge proof that the user paid for a subscription
+/// Starts creating a zero-knowledge proof that the user paid for a subscription
 /// without revealing payment details. This is the core functionality
-/// of fckuipaid.com.
+/// of fckuipaid.com. Returns a job id right away; poll `GET /proofs/{id}`
+/// for the result.
+///
+/// Repeated requests for the same receiver, user, month and challenge, or with
+/// the same `Idempotency-Key` header, return the job already in flight or its
+/// proof, instead of paying for another one.
 async fn generate_proof_handler(
     State(state): State<AppState>,
-    Json(request): Json<GenerateProofRequest>,
-) -> Result<Json<GenerateProofResponse>, StatusCode> {
+    headers: HeaderMap,
+    ValidatedJson(request): ValidatedJson<GenerateProofRequest>,
+) -> Result<(StatusCode, Json<ProofJobResponse>), (StatusCode, Json<ErrorResponse>)> {
     info!("📥 Received proof generation request");
 
-    match state.proof_generator.generate_proof(&request).await {
-        Ok(response) => {
-            info!("✅ Proof generated successfully");
-            Ok(Json(response))
+    let inputs = ProofInputs::from_request(
+        &request,
+        state.nullifiers.policy().current_epoch(),
+        state.verifier.allows_legacy_signatures(),
+    )
+    .map_err(|details| {
+        error!("❌ Invalid proof generation request: {}", details);
+        (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse::validation_error(details)),
+        )
+    })?;
+
+    // Only prove payments to the PaymentReceiver contracts of registered services
+    if state
+        .services
+        .by_receiver(state.proof_generator.chain_id(), inputs.payment_receiver)
+        .is_none()
+    {
+        error!(
+            "❌ No service is registered for receiver {}",
+            inputs.payment_receiver
+        );
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(ErrorResponse::unknown_service(&request.payment_receiver)),
+        ));
+    }
+
+    let client_key = match headers.get("idempotency-key") {
+        Some(value) => Some(
+            value
+                .to_str()
+                .ok()
+                .filter(|key| !key.is_empty() && key.len() <= 255)
+                .ok_or_else(|| {
+                    (
+                        StatusCode::BAD_REQUEST,
+                        Json(ErrorResponse::validation_error(
+                            "Invalid Idempotency-Key header".to_string(),
+                        )),
+                    )
+                })?,
+        ),
+        None => None,
+    };
+    let key = state
+        .jobs
+        .idempotency_key(client_key, &inputs.idempotency_data());
+    let reuse_ttl = state.job_reuse_ttl;
+
+    // Skip the preflight for requests that already have a job
+    if let Some(existing) = existing_job(
+        &state.proof_generator,
+        &state.jobs,
+        &inputs,
+        &key,
+        reuse_ttl,
+    )
+    .await?
+    {
+        return idempotent_response(existing);
+    }
+
+    // Reject requests the guest would fail on before spending prover market funds
+    let (evm_input, block_timestamp) = state
+        .proof_generator
+        .preflight(&inputs)
+        .await
+        .map_err(preflight_error)?;
+
+    // A concurrent request may have created the job during the preflight
+    let job = match state
+        .jobs
+        .create(Some(&key), reuse_ttl)
+        .await
+        .map_err(store_error)?
+    {
+        Idempotent::Created(job) => job,
+        existing => return idempotent_response(existing),
+    };
+    let handle = state.jobs.handle(&job.id);
+    let proof_generator = state.proof_generator.clone();
+    info!("🧾 Created proof job {}", job.id);
+
+    tokio::spawn(async move {
+        match proof_generator
+            .generate_proof(&inputs, evm_input, block_timestamp, &handle)
+            .await
+        {
+            Ok(response) => {
+                info!("✅ Proof generated successfully");
+                handle.complete(response).await;
+            }
+            Err(e) => {
+                error!("❌ Failed to generate proof: {:#}", e);
+                handle.fail(format!("{:#}", e)).await;
+            }
+        }
+    });
+
+    Ok((StatusCode::ACCEPTED, Json(job.into())))
+}
+
+/// Job of an earlier identical request. The request is authorized first, so that
+/// knowing the fields of another user's request does not reveal their job and proof.
+async fn existing_job(
+    proof_generator: &ProofGenerator,
+    jobs: &JobStore,
+    inputs: &ProofInputs,
+    key: &IdempotencyKey,
+    reuse_ttl: std::time::Duration,
+) -> Result<Option<Idempotent>, (StatusCode, Json<ErrorResponse>)> {
+    proof_generator.authorize(inputs).map_err(preflight_error)?;
+    jobs.find(key, reuse_ttl).await.map_err(store_error)
+}
+
+fn preflight_error(e: PreflightError) -> (StatusCode, Json<ErrorResponse>) {
+    error!("❌ Preflight failed: {}", e);
+    (
+        e.status(),
+        Json(ErrorResponse::new(
+            "Preflight check failed",
+            e.reason(),
+            Some(e.to_string()),
+        )),
+    )
+}
+
+fn store_error(e: anyhow::Error) -> (StatusCode, Json<ErrorResponse>) {
+    error!("❌ Proof job store failed: {:#}", e);
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ErrorResponse::proof_generation_error(format!("{:#}", e))),
+    )
+}
+
+/// Response for a request that matched an existing job
+fn idempotent_response(
+    existing: Idempotent,
+) -> Result<(StatusCode, Json<ProofJobResponse>), (StatusCode, Json<ErrorResponse>)> {
+    match existing {
+        Idempotent::Existing(job) => {
+            info!("♻️ Reusing proof job {}", job.id);
+            Ok((StatusCode::OK, Json(job.into())))
         }
+        Idempotent::Created(job) => Ok((StatusCode::ACCEPTED, Json(job.into()))),
+        Idempotent::Conflict => Err((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(ErrorResponse::idempotency_conflict()),
+        )),
+    }
+}
+
+/// 🧪 Dry Run Endpoint
+///
+/// POST /dry-run
+///
+/// Executes the payment check guest for a proof request without proving it.
+/// Reports which guest check panicked, if any, and the cycle counts a proof
+/// would take. Used for debugging requests and sizing market offers.
+///
+/// Only served when `server.enable_dry_run` is set. Like a proof request, the
+/// request must be signed by the user and be for the current period.
+async fn dry_run_handler(
+    State(state): State<AppState>,
+    ValidatedJson(request): ValidatedJson<GenerateProofRequest>,
+) -> Result<Json<DryRunResponse>, (StatusCode, Json<ErrorResponse>)> {
+    info!("📥 Received dry run request");
+
+    let inputs = ProofInputs::from_request(
+        &request,
+        state.nullifiers.policy().current_epoch(),
+        state.verifier.allows_legacy_signatures(),
+    )
+    .map_err(|details| {
+        error!("❌ Invalid dry run request: {}", details);
+        (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse::validation_error(details)),
+        )
+    })?;
+
+    if state
+        .services
+        .by_receiver(state.proof_generator.chain_id(), inputs.payment_receiver)
+        .is_none()
+    {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(ErrorResponse::unknown_service(&request.payment_receiver)),
+        ));
+    }
+
+    // Executing the guest is costly, so only do it for requests their user signed
+    state
+        .proof_generator
+        .authorize(&inputs)
+        .map_err(preflight_error)?;
+
+    let report = state.proof_generator.dry_run(&inputs).await.map_err(|e| {
+        error!("❌ Dry run failed: {:#}", e);
+        (
+            StatusCode::BAD_GATEWAY,
+            Json(ErrorResponse::new(
+                "Dry run failed",
+                "DRY_RUN_ERROR",
+                Some(format!("{:#}", e)),
+            )),
+        )
+    })?;
+
+    Ok(Json(report.into()))
+}
+
+/// 📋 Proof Job Status Endpoint
+///
+/// GET /proofs/{id}
+///
+/// Reports the state of a proof job and returns the generated proof
+/// once the job is fulfilled.
+async fn proof_job_handler(
+    State(state): State<AppState>,
+    Path(job_id): Path<String>,
+) -> Result<Json<ProofJobResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let job = state.jobs.get(&job_id).await.map_err(|e| {
+        error!("❌ Failed to read proof job {}: {:#}", job_id, e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse::proof_generation_error(format!("{:#}", e))),
+        )
+    })?;
+    match job {
+        Some(job) => Ok(Json(job.into())),
+        None => Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse::job_not_found(&job_id)),
+        )),
+    }
+}
+
+/// 🎲 Login Challenge Endpoint
+///
+/// POST /challenges
+///
+/// Issues a single-use random challenge for a login session. The user binds it
+/// into their proof and the service passes it back to `/verify-proof`.
+async fn challenge_handler(State(state): State<AppState>) -> Json<ChallengeResponse> {
+    let (challenge, expires_at) = state.challenges.issue();
+    info!(
+        "🎲 Issued challenge {} (expires at {})",
+        challenge, expires_at
+    );
+
+    Json(ChallengeResponse {
+        challenge: challenge.to_string(),
+        expires_at,
+    })
+}
+
+/// 🔍 Verify ZK Proof Endpoint
+///
+/// POST /verify-proof
+///
+/// Verifies a proof of payment on behalf of a web2 service and returns
+/// whether access should be granted. Denials carry a machine-readable reason.
+async fn verify_proof_handler(
+    State(state): State<AppState>,
+    ValidatedJson(request): ValidatedJson<VerifyProofRequest>,
+) -> Json<VerifyProofResponse> {
+    info!("📥 Received proof verification request");
+
+    let response = verify_proof(&state, &request).await;
+    if response.access_granted {
+        info!("✅ Access granted");
+    } else {
+        info!("🚫 Access denied: {:?}", response.reason);
+    }
+    Json(response)
+}
+
+/// 🛍️ Services Endpoint
+///
+/// GET /services
+///
+/// Lists the services whose subscriptions can be proven and verified.
+async fn services_handler(State(state): State<AppState>) -> Json<Vec<ServiceInfo>> {
+    Json(state.services.services().iter().map(Into::into).collect())
+}
+
+/// 📦 Guest Input Endpoint
+///
+/// GET /inputs/{name}
+///
+/// Serves guest inputs stored by the local storage provider so that
+/// Boundless provers can fetch them.
+async fn input_handler(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Vec<u8>, StatusCode> {
+    let Some(storage) = state.local_inputs.as_ref() else {
+        return Err(StatusCode::NOT_FOUND);
+    };
+
+    match storage.read(&name).await {
+        Ok(Some(bytes)) => Ok(bytes),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
         Err(e) => {
-            error!("❌ Failed to generate proof: {}", e);
+            error!("❌ Failed to read input {}: {:#}", name, e);
             Err(StatusCode::INTERNAL_SERVER_ERROR)
         }
     }
@@ -311,13 +1057,38 @@ async fn health_handler() -> Json<serde_json::Value> {
     Json(serde_json::json!({
         "status": "healthy",
         "service": "fckuipaid-backend",
-        "timestamp": std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs()
+        "timestamp": unix_timestamp()
     }))
 }
 
+/// Resume waiting on the market requests of jobs that were in flight when the
+/// backend stopped, resubmitting them should they expire. Jobs that were not
+/// submitted yet are marked as failed rather than proven again, as a request may
+/// have been submitted without being recorded.
+async fn resume_jobs(jobs: &JobStore, proof_generator: &Arc<ProofGenerator>) -> Result<()> {
+    let unfinished = jobs.unfinished().await?;
+    if !unfinished.is_empty() {
+        info!("🔁 Resuming {} unfinished proof job(s)", unfinished.len());
+    }
+    for job in unfinished {
+        let handle = jobs.handle(&job.id);
+        let proof_generator = proof_generator.clone();
+        tokio::spawn(async move {
+            match proof_generator.resume(&job, &handle).await {
+                Ok(response) => {
+                    info!("✅ Resumed job {} fulfilled", job.id);
+                    handle.complete(response).await;
+                }
+                Err(e) => {
+                    error!("❌ Failed to resume job {}: {:#}", job.id, e);
+                    handle.fail(format!("{:#}", e)).await;
+                }
+            }
+        });
+    }
+    Ok(())
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Load environment variables
@@ -328,32 +1099,272 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     info!("🚀 Starting fckuipaid.com ZK Proof Generator API");
 
-    // Initialize proof generator (will fail if environment not set up)
-    let proof_generator = ProofGenerator::new("fckuipaid.xyz".to_string())
-        .await
-        .map_err(|e| {
-            error!("❌ Failed to initialize proof generator: {}", e);
-            error!("💡 Make sure RPC_URL and PRIVATE_KEY environment variables are set");
-            e
-        })?;
+    // Load the layered configuration, failing fast on invalid settings
+    let config = Config::load().map_err(|e| {
+        error!("❌ Invalid configuration: {:#}", e);
+        e
+    })?;
+    info!("⚙️ Effective configuration:\n{}", config.redacted());
+
+    // Configure where large guest inputs are uploaded to. Only market provers
+    // fetch inputs, local and dev mode proving needs no storage.
+    let input_storage = match config.prover.backend {
+        ProverBackend::Boundless => Some(
+            InputStorageBackend::from_config(
+                &config.storage,
+                config.server.public_base_url.as_ref(),
+            )
+            .map_err(|e| {
+                error!("❌ Failed to configure input storage: {}", e);
+                e
+            })?,
+        ),
+        ProverBackend::Local | ProverBackend::Dev => None,
+    };
+
+    // Select the prover backend. The market prover refuses to start if its
+    // program URL does not serve the guest our verifiers accept.
+    let prover = prover_from_config(
+        &config.prover,
+        input_storage.as_ref().map(InputStorageBackend::provider),
+    )
+    .await
+    .map_err(|e| {
+        error!("❌ Failed to configure prover: {:#}", e);
+        e
+    })?;
+
+    // Nullifier epochs and how many logins a paid account gets per epoch
+    let nullifier_policy = NullifierPolicy {
+        epoch_length: std::time::Duration::from_secs(config.login.nullifier_epoch_secs),
+        max_uses_per_epoch: config.login.nullifier_max_uses,
+    };
+
+    let proof_generator = ProofGenerator::new(
+        config.chain.clone(),
+        std::time::Duration::from_secs(config.login.grace_period_secs),
+        nullifier_policy,
+        prover,
+    );
+
+    // Services whose PaymentReceiver contracts proofs are generated and verified for
+    let services = ServiceRegistry::new(config.services.clone()).map_err(|e| {
+        error!("❌ Invalid services: {:#}", e);
+        e
+    })?;
+    info!("🛍️ Loaded {} service(s)", services.services().len());
+
+    // Steel commitments of verified proofs are checked against the configured chain and
+    // must have been made with the chain spec the guest executes with
+    let commitment_validator = CommitmentValidator::new(
+        Arc::new(CachedHeaderSource::new(
+            RpcHeaderSource::new(config.chain.rpc_url.expose().clone()),
+            config.chain.header_cache_size,
+        )),
+        std::time::Duration::from_secs(config.chain.max_block_age_secs),
+    )
+    .with_config_id(PAYMENT_CHAIN.config_id());
+
+    // Proof jobs are persisted, so requests in flight survive a restart
+    let jobs = JobStore::open(&config.jobs.database).map_err(|e| {
+        error!("❌ Failed to open job store: {:#}", e);
+        e
+    })?;
+    let proof_generator = Arc::new(proof_generator);
+    resume_jobs(&jobs, &proof_generator).await?;
 
     let state = AppState {
-        proof_generator: Arc::new(proof_generator),
+        proof_generator,
+        jobs,
+        job_reuse_ttl: std::time::Duration::from_secs(config.jobs.proof_reuse_ttl_secs),
+        verifier: services
+            .services()
+            .iter()
+            .fold(Verifier::new([PAYMENT_CHECK_ID]), |verifier, service| {
+                verifier.with_payment_receiver(service.chain_id, service.payment_receiver)
+            })
+            .allow_legacy_signatures(config.login.allow_legacy_signatures)
+            .with_max_block_age(std::time::Duration::from_secs(
+                config.chain.max_block_age_secs,
+            ))
+            .with_max_grace_period(std::time::Duration::from_secs(
+                config.login.grace_period_secs,
+            )),
+        commitment_validator,
+        challenges: Arc::new(ChallengeRegistry::new(
+            std::time::Duration::from_secs(config.login.challenge_ttl_secs),
+            config.login.max_outstanding_challenges,
+        )),
+        nullifiers: Arc::new(NullifierTracker::new(nullifier_policy)),
+        services: Arc::new(services),
+        local_inputs: input_storage.as_ref().and_then(InputStorageBackend::local),
     };
 
     // Build the application router
-    let app = Router::new()
-        .route("/health", axum::routing::get(health_handler))
+    let mut routes = Router::new()
+        .route("/health", get(health_handler))
+        .route("/services", get(services_handler))
         .route("/generate-proof", post(generate_proof_handler))
-        .layer(CorsLayer::permissive())
-        .with_state(state);
+        .route("/proofs/:id", get(proof_job_handler))
+        .route("/inputs/:name", get(input_handler))
+        .route("/challenges", post(challenge_handler))
+        .route("/verify-proof", post(verify_proof_handler));
+    if config.server.enable_dry_run {
+        info!("🧪 Dry runs enabled at POST /dry-run");
+        routes = routes.route("/dry-run", post(dry_run_handler));
+    }
+    let app = routes.layer(CorsLayer::permissive()).with_state(state);
 
     // Start the server
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3001").await?;
-    info!("📡 fckuipaid-backend listening on http://0.0.0.0:3001");
+    let listener = tokio::net::TcpListener::bind(config.server.listen_addr).await?;
+    info!(
+        "📡 fckuipaid-backend listening on http://{}",
+        config.server.listen_addr
+    );
     info!("🔮 Ready to generate ZK proofs!");
 
     axum::serve(listener, app).await?;
 
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use alloy::signers::{local::PrivateKeySigner, SignerSync};
+    use alloy_primitives::address;
+
+    use super::*;
+    use crate::prover::DevModeProver;
+
+    const RECEIVER: Address = address!("6fEDEb0B4942A8b438AFE68ba7c8Af4637c41903");
+    const REUSE_TTL: Duration = Duration::from_secs(3600);
+
+    fn proof_generator() -> ProofGenerator {
+        let chain = toml::from_str("rpc_url = \"http://localhost:8545\"").unwrap();
+        ProofGenerator::new(
+            chain,
+            Duration::from_secs(3 * 86400),
+            NullifierPolicy {
+                epoch_length: Duration::from_secs(86400),
+                max_uses_per_epoch: 10,
+            },
+            Arc::new(DevModeProver),
+        )
+    }
+
+    fn signer(byte: u8) -> PrivateKeySigner {
+        PrivateKeySigner::from_bytes(&B256::repeat_byte(byte)).unwrap()
+    }
+
+    /// Inputs for `user` with the login authorization signed by `signer`
+    fn signed_inputs(
+        generator: &ProofGenerator,
+        user: &PrivateKeySigner,
+        signer: &PrivateKeySigner,
+    ) -> ProofInputs {
+        let period = BillingPeriod::current();
+        let expiry = unix_timestamp() + 600;
+        let authorization = LoginAuthorization {
+            paymentReceiver: RECEIVER,
+            chainId: generator.chain_id(),
+            month: period.to_onchain(),
+            expiry,
+        };
+        let signature = signer
+            .sign_hash_sync(&authorization.signing_hash())
+            .unwrap();
+        let nullifier_key = user
+            .sign_message_sync(NULLIFIER_KEY_MESSAGE.as_bytes())
+            .unwrap();
+        ProofInputs {
+            user_address: user.address(),
+            payment_receiver: RECEIVER,
+            period,
+            signature: signature.as_bytes().to_vec(),
+            nullifier_key: nullifier_key.as_bytes().to_vec(),
+            challenge: B256::repeat_byte(7),
+            epoch: 1,
+            expiry,
+            legacy_signature: false,
+        }
+    }
+
+    #[test]
+    fn nullifier_key_must_be_signed_by_the_user() {
+        let generator = proof_generator();
+        let user = signer(1);
+        let mut inputs = signed_inputs(&generator, &user, &user);
+        generator.authorize(&inputs).unwrap();
+
+        let other_key = signer(2)
+            .sign_message_sync(NULLIFIER_KEY_MESSAGE.as_bytes())
+            .unwrap();
+        inputs.nullifier_key = other_key.as_bytes().to_vec();
+        let err = generator.authorize(&inputs).unwrap_err();
+        assert_eq!(err.reason(), "BAD_NULLIFIER_KEY");
+    }
+
+    #[tokio::test]
+    async fn other_epoch_gets_another_job() {
+        let generator = proof_generator();
+        let database = std::env::temp_dir()
+            .join(format!("fckuipaid-main-{}", uuid::Uuid::new_v4()))
+            .join("jobs.sqlite");
+        let jobs = JobStore::open(&database).unwrap();
+
+        let user = signer(1);
+        let inputs = signed_inputs(&generator, &user, &user);
+        let next_epoch = ProofInputs {
+            epoch: inputs.epoch + 1,
+            ..inputs.clone()
+        };
+
+        let mut ids = Vec::new();
+        for inputs in [&inputs, &next_epoch] {
+            let key = jobs.idempotency_key(None, &inputs.idempotency_data());
+            match jobs.create(Some(&key), REUSE_TTL).await.unwrap() {
+                Idempotent::Created(job) => ids.push(job.id),
+                other => panic!("expected a new job, got {:?}", other),
+            }
+        }
+        assert_ne!(ids[0], ids[1]);
+    }
+
+    #[tokio::test]
+    async fn wrong_signature_does_not_reuse_the_job() {
+        let generator = proof_generator();
+        let database = std::env::temp_dir()
+            .join(format!("fckuipaid-main-{}", uuid::Uuid::new_v4()))
+            .join("jobs.sqlite");
+        let jobs = JobStore::open(&database).unwrap();
+
+        let user = signer(1);
+        let inputs = signed_inputs(&generator, &user, &user);
+        let key = jobs.idempotency_key(Some("client key"), &inputs.idempotency_data());
+        let job = match jobs.create(Some(&key), REUSE_TTL).await.unwrap() {
+            Idempotent::Created(job) => job,
+            other => panic!("expected a new job, got {:?}", other),
+        };
+
+        match existing_job(&generator, &jobs, &inputs, &key, REUSE_TTL).await {
+            Ok(Some(Idempotent::Existing(existing))) => assert_eq!(existing.id, job.id),
+            other => panic!(
+                "expected the existing job, got {:?}",
+                other.map_err(|e| e.0)
+            ),
+        }
+
+        // The same request signed by someone else, with and without the client key
+        let forged = signed_inputs(&generator, &user, &signer(2));
+        assert_ne!(forged.idempotency_data(), inputs.idempotency_data());
+        for key in [key, jobs.idempotency_key(None, &forged.idempotency_data())] {
+            let (status, Json(error)) = existing_job(&generator, &jobs, &forged, &key, REUSE_TTL)
+                .await
+                .unwrap_err();
+            assert_eq!(status, StatusCode::UNAUTHORIZED);
+            assert_eq!(error.error_code, "BAD_SIGNATURE");
+        }
+    }
+}
@@ -0,0 +1,229 @@
+use alloy_primitives::U256;
+use boundless_market::request_builder::OfferParams;
+
+use crate::config::OfferConfig;
+use crate::types::OfferInfo;
+
+/// Offer of a Boundless request, priced by the cycles of the guest execution
+#[derive(Debug, Clone)]
+pub struct Offer {
+    /// Proven cycles the offer is priced for
+    pub cycles: u64,
+    /// Whether the cycles were measured by executing the guest
+    pub cycles_measured: bool,
+    pub min_price: U256,
+    pub max_price: U256,
+    pub ramp_up_period: u32,
+    pub lock_timeout: u32,
+    pub timeout: u32,
+    pub lock_stake: U256,
+}
+
+impl Offer {
+    /// Apply the price and timeout policy to a cycle count
+    pub fn new(policy: &OfferConfig, cycles: u64, cycles_measured: bool) -> Self {
+        let mcycles = cycles.div_ceil(1_000_000).max(1);
+        let extra_time = u32::try_from(mcycles)
+            .unwrap_or(u32::MAX)
+            .saturating_mul(policy.timeout_secs_per_mcycle);
+
+        Self {
+            cycles,
+            cycles_measured,
+            min_price: U256::from(policy.min_price_per_mcycle) * U256::from(mcycles),
+            max_price: U256::from(policy.max_price_per_mcycle) * U256::from(mcycles),
+            ramp_up_period: policy.ramp_up_period_secs,
+            lock_timeout: policy.lock_timeout_secs.saturating_add(extra_time),
+            timeout: policy.timeout_secs.saturating_add(extra_time),
+            lock_stake: U256::from(policy.lock_stake),
+        }
+    }
+
+    /// Offer priced by `cycle_estimate`, for when the cycles are not measured
+    pub fn estimated(policy: &OfferConfig) -> Self {
+        Self::new(policy, policy.cycle_estimate, false)
+    }
+
+    /// Offer with the max price capped at `budget`, and the min price at the max price
+    pub fn capped(&self, budget: U256) -> Self {
+        let max_price = self.max_price.min(budget);
+        Self {
+            min_price: self.min_price.min(max_price),
+            max_price,
+            ..self.clone()
+        }
+    }
+
+    /// Offer for a resubmission, with prices raised by `percent` and the
+    /// max price capped at `budget`
+    pub fn escalated(&self, percent: u32, budget: U256) -> Self {
+        let escalate = |price: U256| {
+            price.saturating_mul(U256::from(100 + u64::from(percent))) / U256::from(100)
+        };
+        Self {
+            min_price: escalate(self.min_price),
+            max_price: escalate(self.max_price),
+            ..self.clone()
+        }
+        .capped(budget)
+    }
+
+    /// Offer parameters of the market request
+    pub fn params(&self) -> OfferParams {
+        OfferParams::builder()
+            .min_price(self.min_price)
+            .max_price(self.max_price)
+            .ramp_up_period(self.ramp_up_period)
+            .lock_timeout(self.lock_timeout)
+            .timeout(self.timeout)
+            .lock_stake(self.lock_stake)
+            .into()
+    }
+}
+
+impl From<&Offer> for OfferInfo {
+    fn from(offer: &Offer) -> Self {
+        Self {
+            cycles: offer.cycles,
+            cycles_measured: offer.cycles_measured,
+            min_price: offer.min_price.to_string(),
+            max_price: offer.max_price.to_string(),
+            ramp_up_period: offer.ramp_up_period,
+            lock_timeout: offer.lock_timeout,
+            timeout: offer.timeout,
+            lock_stake: offer.lock_stake.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> OfferConfig {
+        OfferConfig {
+            min_price_per_mcycle: 100,
+            max_price_per_mcycle: 1_000,
+            ramp_up_period_secs: 60,
+            lock_timeout_secs: 600,
+            timeout_secs: 1200,
+            timeout_secs_per_mcycle: 2,
+            lock_stake: 5,
+            measure_cycles: true,
+            cycle_estimate: 20_000_000,
+        }
+    }
+
+    #[test]
+    fn prices_started_mcycles() {
+        let offer = Offer::new(&policy(), 2_500_000, true);
+        assert_eq!(offer.cycles, 2_500_000);
+        assert!(offer.cycles_measured);
+        assert_eq!(offer.min_price, U256::from(300));
+        assert_eq!(offer.max_price, U256::from(3_000));
+        assert_eq!(offer.lock_stake, U256::from(5));
+        assert_eq!(offer.ramp_up_period, 60);
+
+        let offer = Offer::new(&policy(), 3_000_000, true);
+        assert_eq!(offer.min_price, U256::from(300));
+        assert_eq!(offer.max_price, U256::from(3_000));
+    }
+
+    #[test]
+    fn prices_at_least_one_mcycle() {
+        for cycles in [0, 1, 1_000_000] {
+            let offer = Offer::new(&policy(), cycles, true);
+            assert_eq!(offer.min_price, U256::from(100));
+            assert_eq!(offer.max_price, U256::from(1_000));
+            assert_eq!(offer.lock_timeout, 602);
+            assert_eq!(offer.timeout, 1202);
+        }
+    }
+
+    #[test]
+    fn extends_timeouts_per_mcycle() {
+        let offer = Offer::new(&policy(), 20_000_000, true);
+        assert_eq!(offer.lock_timeout, 640);
+        assert_eq!(offer.timeout, 1240);
+
+        let offer = Offer::new(&policy(), u64::MAX, true);
+        assert_eq!(offer.lock_timeout, u32::MAX);
+        assert_eq!(offer.timeout, u32::MAX);
+    }
+
+    #[test]
+    fn falls_back_to_the_cycle_estimate() {
+        let offer = Offer::estimated(&policy());
+        assert_eq!(offer.cycles, 20_000_000);
+        assert!(!offer.cycles_measured);
+        assert_eq!(offer.min_price, U256::from(2_000));
+        assert_eq!(offer.max_price, U256::from(20_000));
+    }
+
+    #[test]
+    fn reports_prices_as_decimal_strings() {
+        let info = OfferInfo::from(&Offer::new(&policy(), 2_500_000, false));
+        assert_eq!(info.cycles, 2_500_000);
+        assert!(!info.cycles_measured);
+        assert_eq!(info.min_price, "300");
+        assert_eq!(info.max_price, "3000");
+        assert_eq!(info.lock_stake, "5");
+    }
+
+    #[test]
+    fn caps_prices_at_the_budget() {
+        let offer = Offer::new(&policy(), 2_500_000, true);
+        let capped = offer.capped(U256::from(2_000));
+        assert_eq!(capped.min_price, U256::from(300));
+        assert_eq!(capped.max_price, U256::from(2_000));
+
+        let capped = offer.capped(U256::from(200));
+        assert_eq!(capped.min_price, U256::from(200));
+        assert_eq!(capped.max_price, U256::from(200));
+
+        let capped = offer.capped(U256::from(10_000));
+        assert_eq!(capped.min_price, offer.min_price);
+        assert_eq!(capped.max_price, offer.max_price);
+    }
+
+    #[test]
+    fn escalates_prices_by_percent() {
+        let offer = Offer::new(&policy(), 2_500_000, true);
+        let escalated = offer.escalated(50, U256::from(10_000));
+        assert_eq!(escalated.min_price, U256::from(450));
+        assert_eq!(escalated.max_price, U256::from(4_500));
+        assert_eq!(escalated.cycles, offer.cycles);
+        assert_eq!(escalated.timeout, offer.timeout);
+
+        let escalated = escalated.escalated(50, U256::from(10_000));
+        assert_eq!(escalated.min_price, U256::from(675));
+        assert_eq!(escalated.max_price, U256::from(6_750));
+
+        let unchanged = offer.escalated(0, U256::from(10_000));
+        assert_eq!(unchanged.min_price, offer.min_price);
+        assert_eq!(unchanged.max_price, offer.max_price);
+    }
+
+    #[test]
+    fn never_escalates_beyond_the_budget() {
+        let budget = U256::from(4_000);
+        let mut offer = Offer::new(&policy(), 2_500_000, true);
+        for _ in 0..10 {
+            offer = offer.escalated(50, budget);
+            assert!(offer.max_price <= budget);
+            assert!(offer.min_price <= offer.max_price);
+        }
+        assert_eq!(offer.max_price, budget);
+        assert_eq!(offer.min_price, budget);
+
+        // An offer above the budget is lowered rather than kept
+        let offer = Offer::new(&policy(), 10_000_000, true).escalated(50, budget);
+        assert_eq!(offer.max_price, budget);
+
+        let offer = Offer::new(&policy(), 2_500_000, true).escalated(u32::MAX, U256::MAX);
+        assert_eq!(
+            offer.max_price,
+            U256::from(3_000u64 * (100 + u32::MAX as u64) / 100)
+        );
+    }
+}
@@ -0,0 +1,69 @@
+use anyhow::{ensure, Context, Result};
+use risc0_zkvm::{compute_image_id, sha::Digest};
+use tracing::info;
+use url::Url;
+
+/// Program URL whose content was checked to be the payment check guest. The only
+/// way to get one is [`VerifiedProgram::fetch`], so a new URL is always re-checked.
+#[derive(Debug, Clone)]
+pub struct VerifiedProgram {
+    url: Url,
+    image_id: Digest,
+}
+
+impl VerifiedProgram {
+    /// Fetch the program at `url` and check that its image ID is `expected`
+    pub async fn fetch(url: &Url, expected: Digest) -> Result<Self> {
+        let elf = download_elf(url).await?;
+        let image_id = compute_image_id(&elf)
+            .with_context(|| format!("{} does not serve a guest program", url))?;
+        ensure!(
+            image_id == expected,
+            "program at {} has image ID {}, expected {}",
+            url,
+            image_id,
+            expected
+        );
+        info!("🔏 Verified program {} with image ID {}", url, image_id);
+
+        Ok(Self {
+            url: url.clone(),
+            image_id,
+        })
+    }
+
+    /// URL provers download the program from
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    /// Image ID of the program
+    pub fn image_id(&self) -> Digest {
+        self.image_id
+    }
+}
+
+/// Download the ELF binary the way provers do, e.g. from an IPFS gateway
+async fn download_elf(url: &Url) -> Result<Vec<u8>> {
+    info!("📡 Downloading ELF from {}", url);
+
+    let response = reqwest::get(url.clone())
+        .await
+        .context("Failed to make HTTP request for the ELF")?;
+
+    if !response.status().is_success() {
+        return Err(anyhow::anyhow!(
+            "ELF request failed with status: {}",
+            response.status()
+        ));
+    }
+
+    let elf_bytes = response
+        .bytes()
+        .await
+        .context("Failed to read ELF bytes from response")?
+        .to_vec();
+
+    info!("✅ Successfully downloaded {} bytes", elf_bytes.len());
+    Ok(elf_bytes)
+}
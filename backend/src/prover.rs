@@ -0,0 +1,599 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use alloy::signers::local::PrivateKeySigner;
+use alloy_primitives::U256;
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use boundless_market::{contracts::RequestStatus, input::GuestEnv, Client};
+use fckuipaid_methods::{GuestFailure, PAYMENT_CHECK_ELF, PAYMENT_CHECK_ID};
+use fckuipaid_verifier::unix_timestamp;
+use risc0_ethereum_contracts::encode_seal;
+use risc0_zkvm::{
+    default_executor, default_prover, ExecutorEnv, FakeReceipt, InnerReceipt, ProverOpts, Receipt,
+    VerifierContext,
+};
+use tracing::{info, warn};
+use url::Url;
+
+use crate::config::{OfferConfig, ProverBackend, ProverConfig, RetryConfig};
+use crate::jobs::JobHandle;
+use crate::offer::Offer;
+use crate::program::VerifiedProgram;
+use crate::storage::{upload_if_too_large, InputStorage};
+use crate::types::{DryRunResponse, JobStatus, SegmentCyclesInfo};
+
+/// Journal and ABI-encoded seal of a proven guest execution
+pub struct ProverOutput {
+    pub journal: Vec<u8>,
+    pub seal: Vec<u8>,
+}
+
+/// Backend that turns guest stdin into a proof of the payment check program
+#[async_trait]
+pub trait Prover: Send + Sync {
+    /// Name of the backend for logging and proof ids
+    fn name(&self) -> &'static str;
+
+    /// Prove the payment check guest for the given stdin, reporting progress on `job`.
+    /// Proofs only verify until `valid_until`, when their block gets too old.
+    async fn prove(
+        &self,
+        stdin: Vec<u8>,
+        valid_until: u64,
+        job: &JobHandle,
+    ) -> Result<ProverOutput>;
+
+    /// Resume a job whose market request was submitted before the backend restarted,
+    /// continuing with the remaining attempts should the request not be fulfilled
+    async fn resume(
+        &self,
+        _stdin: Vec<u8>,
+        request: SubmittedRequest,
+        _job: &JobHandle,
+    ) -> Result<ProverOutput> {
+        bail!(
+            "the {} prover cannot resume request {}",
+            self.name(),
+            request.request_id
+        )
+    }
+}
+
+/// Market request submitted before the backend restarted
+pub struct SubmittedRequest {
+    /// Boundless request id as hex string
+    pub request_id: String,
+    pub expires_at: u64,
+    /// Market attempts made so far, including this request
+    pub attempt: u32,
+    /// When proofs of the request's input stop verifying
+    pub valid_until: u64,
+}
+
+/// How waiting for a submitted market request ended
+enum RequestOutcome {
+    Fulfilled(ProverOutput),
+    /// The request expired unfulfilled and can be resubmitted
+    Expired,
+}
+
+/// Proves through the Boundless prover market
+pub struct BoundlessProver {
+    /// Ethereum Sepolia RPC URL for Boundless Market
+    rpc_url: Url,
+    /// Key funding the market requests
+    private_key: PrivateKeySigner,
+    /// Program provers download, checked to be the payment check guest
+    program: VerifiedProgram,
+    /// Storage for guest inputs exceeding the inline limit
+    input_storage: Arc<dyn InputStorage>,
+    /// How often the request status is polled
+    poll_interval: Duration,
+    /// Price and timeout policy of the requests
+    offer_policy: OfferConfig,
+    /// Resubmission of requests that expire unfulfilled
+    retry_policy: RetryConfig,
+}
+
+impl BoundlessProver {
+    pub fn new(
+        rpc_url: Url,
+        private_key: PrivateKeySigner,
+        program: VerifiedProgram,
+        input_storage: Arc<dyn InputStorage>,
+        poll_interval: Duration,
+        offer_policy: OfferConfig,
+        retry_policy: RetryConfig,
+    ) -> Self {
+        Self {
+            rpc_url,
+            private_key,
+            program,
+            input_storage,
+            poll_interval,
+            offer_policy,
+            retry_policy,
+        }
+    }
+
+    /// Poll a submitted request until it is fulfilled or expires. RPC errors are
+    /// retried on the same request, as it may still be fulfilled. Only once it has
+    /// expired without its status being known is waiting given up.
+    async fn wait(
+        &self,
+        request_id: U256,
+        expires_at: u64,
+        job: &JobHandle,
+    ) -> Result<RequestOutcome> {
+        let client = Client::builder()
+            .with_rpc_url(self.rpc_url.clone())
+            .with_private_key(self.private_key.clone())
+            .build()
+            .await?;
+
+        loop {
+            let result = match client
+                .boundless_market
+                .get_status(request_id, Some(expires_at))
+                .await
+            {
+                Ok(RequestStatus::Fulfilled) => {
+                    match client
+                        .wait_for_request_fulfillment(request_id, self.poll_interval, expires_at)
+                        .await
+                    {
+                        Ok((journal, seal)) => {
+                            info!("✅ Request {:x} fulfilled", request_id);
+                            return Ok(RequestOutcome::Fulfilled(ProverOutput {
+                                journal: journal.to_vec(),
+                                seal: seal.to_vec(),
+                            }));
+                        }
+                        Err(e) => {
+                            Err(anyhow::Error::from(e).context("Failed to fetch the fulfillment"))
+                        }
+                    }
+                }
+                Ok(RequestStatus::Locked) => {
+                    job.set_status(JobStatus::Locked).await;
+                    Ok(())
+                }
+                Ok(RequestStatus::Expired) => {
+                    job.set_status(JobStatus::Expired).await;
+                    return Ok(RequestOutcome::Expired);
+                }
+                Ok(RequestStatus::Unknown) => Ok(()),
+                Err(e) => Err(anyhow::Error::from(e).context("Failed to get the request status")),
+            };
+
+            if let Err(e) = result {
+                if unix_timestamp() > expires_at {
+                    return Err(e.context(format!(
+                        "Request {:x} expired while its status could not be checked",
+                        request_id
+                    )));
+                }
+                warn!(
+                    "⚠️ Polling request {:x} failed, retrying: {:#}",
+                    request_id, e
+                );
+            }
+            tokio::time::sleep(self.poll_interval).await;
+        }
+    }
+
+    /// Submit requests until one is fulfilled, resubmitting expired ones at higher
+    /// prices, and fall back to the local prover once all attempts failed if configured.
+    /// A request submitted earlier, with its id, expiry and attempt number, is waited
+    /// for first. No request is submitted that could be fulfilled after `valid_until`.
+    async fn fulfill(
+        &self,
+        stdin: Vec<u8>,
+        mut pending: Option<(U256, u64, u32)>,
+        valid_until: u64,
+        job: &JobHandle,
+    ) -> Result<ProverOutput> {
+        // Create a Boundless client from the provided parameters
+        let client = Client::builder()
+            .with_rpc_url(self.rpc_url.clone())
+            .with_private_key(self.private_key.clone())
+            .build()
+            .await?;
+
+        let retry = &self.retry_policy;
+        let mut attempt = pending.map_or(0, |(_, _, attempt)| attempt);
+        // Priced and uploaded once, when the first request is submitted
+        let mut offer = None;
+        let mut input_url = None;
+        let mut last_error = None;
+        loop {
+            let result = match pending.take() {
+                Some((request_id, expires_at, _)) => self.wait(request_id, expires_at, job).await,
+                None if attempt < retry.max_attempts => {
+                    attempt += 1;
+                    async {
+                        if offer.is_none() {
+                            offer = Some(self.first_offer(&stdin, attempt - 1).await?);
+                        }
+                        if input_url.is_none() {
+                            input_url = Some(self.upload_input(&stdin).await?);
+                        }
+                        let offer = offer.as_ref().expect("priced above");
+                        // Paying for a proof that no longer verifies once delivered is a waste
+                        let latest_fulfillment = unix_timestamp() + u64::from(offer.timeout);
+                        if latest_fulfillment > valid_until {
+                            bail!(
+                                "a new request could be fulfilled until {}, but its proof stops \
+                                 verifying at {} when the block gets too old",
+                                latest_fulfillment,
+                                valid_until
+                            );
+                        }
+                        job.set_offer(offer.into()).await;
+
+                        let boundless_request = client
+                            .new_request()
+                            .with_program_url(self.program.url().clone())?
+                            // Pin the verified image ID, so the request cannot be fulfilled for
+                            // whatever the program URL serves later
+                            .with_image_id(self.program.image_id())
+                            .with_offer(offer.params());
+                        let boundless_request = match input_url.as_ref().expect("uploaded above") {
+                            Some(input_url) => {
+                                boundless_request.with_input_url(input_url.clone())?
+                            }
+                            None => boundless_request.with_stdin(stdin.clone()),
+                        };
+
+                        let (request_id, expires_at) = client
+                            .submit_offchain(boundless_request)
+                            .await
+                            .context("Failed to submit the request")?;
+                        job.submitted(format!("{:x}", request_id), expires_at).await;
+
+                        info!(
+                            "⏳ Waiting for request {:x} to be fulfilled (attempt {}/{})",
+                            request_id, attempt, retry.max_attempts
+                        );
+                        self.wait(request_id, expires_at, job).await
+                    }
+                    .await
+                }
+                None => break,
+            };
+
+            match result {
+                Ok(RequestOutcome::Fulfilled(output)) => return Ok(output),
+                // Only an expired request is resubmitted, at a higher price
+                Ok(RequestOutcome::Expired) => {
+                    warn!("⚠️ Attempt {} expired unfulfilled", attempt);
+                    job.attempt_failed(self.name(), "request expired unfulfilled".to_string())
+                        .await;
+                    last_error = Some(anyhow::anyhow!("request expired unfulfilled"));
+                    offer = offer.map(|offer| {
+                        offer.escalated(
+                            retry.price_escalation_percent,
+                            U256::from(retry.max_price_budget),
+                        )
+                    });
+                }
+                // Submitting again while an earlier request may still be fulfilled
+                // could pay for two proofs, so other failures end the market attempts
+                Err(e) => {
+                    warn!("⚠️ Attempt {} failed: {:#}", attempt, e);
+                    job.attempt_failed(self.name(), format!("{:#}", e)).await;
+                    last_error = Some(e);
+                    break;
+                }
+            }
+        }
+
+        if retry.fallback_to_local && unix_timestamp() < valid_until {
+            info!("🏠 Market attempts failed, falling back to the local prover");
+            job.attempt_started(LocalProver.name()).await;
+            return LocalProver.prove(stdin, valid_until, job).await;
+        }
+        Err(last_error
+            .unwrap_or_else(|| anyhow::anyhow!("no market attempts configured"))
+            .context(format!("No proof after {} market attempts", attempt)))
+    }
+
+    /// Offer of the first request submitted, capped at the budget and escalated
+    /// once for each of the `previous_attempts` that expired
+    async fn first_offer(&self, stdin: &[u8], previous_attempts: u32) -> Result<Offer> {
+        let retry = &self.retry_policy;
+        let budget = U256::from(retry.max_price_budget);
+        let mut offer = self.offer(stdin).await?;
+        if offer.max_price > budget {
+            warn!(
+                "⚠️ Max price {} of {} cycles exceeds the budget, offering {}",
+                offer.max_price, offer.cycles, budget
+            );
+            offer = offer.capped(budget);
+        }
+        for _ in 0..previous_attempts {
+            offer = offer.escalated(retry.price_escalation_percent, budget);
+        }
+        Ok(offer)
+    }
+
+    /// Small inputs go inline with the request, larger ones are uploaded once and
+    /// referenced by URL from every attempt
+    async fn upload_input(&self, stdin: &[u8]) -> Result<Option<Url>> {
+        let encoded_input = GuestEnv::from_stdin(stdin.to_vec())
+            .encode()
+            .context("Failed to encode guest input")?;
+        upload_if_too_large(self.input_storage.as_ref(), &encoded_input).await
+    }
+
+    /// Price the request by the measured cycles of the guest, or by the estimate
+    async fn offer(&self, stdin: &[u8]) -> Result<Offer> {
+        if !self.offer_policy.measure_cycles {
+            return Ok(Offer::estimated(&self.offer_policy));
+        }
+
+        let report = execute(stdin.to_vec()).await?;
+        if let Some((failure, message)) = report.failure {
+            // A request the guest fails on would never be fulfilled
+            bail!("guest execution failed ({}): {}", failure.reason(), message);
+        }
+        info!(
+            "📏 Measured {} cycles ({} segments)",
+            report.total_cycles(),
+            report.segments.len()
+        );
+        Ok(Offer::new(&self.offer_policy, report.total_cycles(), true))
+    }
+}
+
+#[async_trait]
+impl Prover for BoundlessProver {
+    fn name(&self) -> &'static str {
+        "boundless"
+    }
+
+    async fn prove(
+        &self,
+        stdin: Vec<u8>,
+        valid_until: u64,
+        job: &JobHandle,
+    ) -> Result<ProverOutput> {
+        info!("🔮 Generating ZK proof with Boundless SDK");
+        info!("📥 Using guest program from: {}", self.program.url());
+        self.fulfill(stdin, None, valid_until, job).await
+    }
+
+    async fn resume(
+        &self,
+        stdin: Vec<u8>,
+        request: SubmittedRequest,
+        job: &JobHandle,
+    ) -> Result<ProverOutput> {
+        let request_id = U256::from_str_radix(&request.request_id, 16)
+            .with_context(|| format!("Invalid request id {}", request.request_id))?;
+        info!("⏳ Resuming wait for request {:x}", request_id);
+        self.fulfill(
+            stdin,
+            Some((request_id, request.expires_at, request.attempt)),
+            request.valid_until,
+            job,
+        )
+        .await
+    }
+}
+
+/// Proves on this machine with the RISC Zero zkVM, producing a Groth16 seal
+pub struct LocalProver;
+
+#[async_trait]
+impl Prover for LocalProver {
+    fn name(&self) -> &'static str {
+        "local"
+    }
+
+    async fn prove(
+        &self,
+        stdin: Vec<u8>,
+        _valid_until: u64,
+        job: &JobHandle,
+    ) -> Result<ProverOutput> {
+        info!("🔮 Generating ZK proof with the local prover");
+        job.set_status(JobStatus::Proving).await;
+
+        let receipt = tokio::task::spawn_blocking(move || -> Result<Receipt> {
+            let env = ExecutorEnv::builder().write_slice(&stdin).build()?;
+            let prove_info = default_prover().prove_with_ctx(
+                env,
+                &VerifierContext::default(),
+                PAYMENT_CHECK_ELF,
+                &ProverOpts::groth16(),
+            )?;
+            Ok(prove_info.receipt)
+        })
+        .await?
+        .context("failed to create proof")?;
+
+        let seal = encode_seal(&receipt).context("invalid receipt")?;
+        Ok(ProverOutput {
+            journal: receipt.journal.bytes,
+            seal,
+        })
+    }
+}
+
+/// Executes the guest without proving and returns a fake receipt.
+/// Only accepted by verifiers running with `RISC0_DEV_MODE` enabled.
+pub struct DevModeProver;
+
+#[async_trait]
+impl Prover for DevModeProver {
+    fn name(&self) -> &'static str {
+        "dev"
+    }
+
+    async fn prove(
+        &self,
+        stdin: Vec<u8>,
+        _valid_until: u64,
+        job: &JobHandle,
+    ) -> Result<ProverOutput> {
+        info!("🧪 Executing guest in dev mode, the receipt will not be a real proof");
+        job.set_status(JobStatus::Proving).await;
+
+        let receipt = tokio::task::spawn_blocking(move || -> Result<Receipt> {
+            let env = ExecutorEnv::builder().write_slice(&stdin).build()?;
+            let session = default_executor().execute(env, PAYMENT_CHECK_ELF)?;
+            let claim = session
+                .receipt_claim
+                .context("executor did not return a receipt claim")?;
+            Ok(Receipt::new(
+                InnerReceipt::Fake(FakeReceipt::new(claim)),
+                session.journal.bytes,
+            ))
+        })
+        .await?
+        .context("failed to execute guest")?;
+
+        let seal = encode_seal(&receipt).context("invalid receipt")?;
+        Ok(ProverOutput {
+            journal: receipt.journal.bytes,
+            seal,
+        })
+    }
+}
+
+/// Cycles of one segment of an executed session
+pub struct SegmentCycles {
+    /// Segment size as power of two
+    pub po2: u32,
+    /// Cycles spent executing guest instructions
+    pub cycles: u32,
+}
+
+/// Outcome of executing the guest without proving
+pub struct ExecutionReport {
+    /// Guest check that panicked, if the execution failed
+    pub failure: Option<(GuestFailure, String)>,
+    /// Cycles per segment, empty if the execution failed
+    pub segments: Vec<SegmentCycles>,
+    /// Journal of a successful execution
+    pub journal: Option<Vec<u8>>,
+}
+
+impl ExecutionReport {
+    /// Cycles spent executing guest instructions
+    pub fn user_cycles(&self) -> u64 {
+        self.segments.iter().map(|s| s.cycles as u64).sum()
+    }
+
+    /// Cycles that are proven, including paging and padding to the segment size.
+    /// This is what prover market offers are priced by.
+    pub fn total_cycles(&self) -> u64 {
+        self.segments.iter().map(|s| 1u64 << s.po2).sum()
+    }
+}
+
+impl From<ExecutionReport> for DryRunResponse {
+    fn from(report: ExecutionReport) -> Self {
+        let (failure, panic_message) = match &report.failure {
+            Some((failure, message)) => (Some(failure.reason().to_string()), Some(message.clone())),
+            None => (None, None),
+        };
+        Self {
+            success: report.failure.is_none(),
+            failure,
+            panic_message,
+            user_cycles: report.user_cycles(),
+            total_cycles: report.total_cycles(),
+            segments: report
+                .segments
+                .iter()
+                .map(|segment| SegmentCyclesInfo {
+                    po2: segment.po2,
+                    cycles: segment.cycles,
+                })
+                .collect(),
+            journal: report.journal.as_ref().map(hex::encode),
+        }
+    }
+}
+
+/// Execute the payment check guest for the given stdin without proving, to find out
+/// which check fails and how many cycles a proof would take
+pub async fn execute(stdin: Vec<u8>) -> Result<ExecutionReport> {
+    tokio::task::spawn_blocking(move || -> Result<ExecutionReport> {
+        let env = ExecutorEnv::builder().write_slice(&stdin).build()?;
+        let report = match default_executor().execute(env, PAYMENT_CHECK_ELF) {
+            Ok(session) => ExecutionReport {
+                failure: None,
+                segments: session
+                    .segments
+                    .iter()
+                    .map(|segment| SegmentCycles {
+                        po2: segment.po2,
+                        cycles: segment.cycles,
+                    })
+                    .collect(),
+                journal: Some(session.journal.bytes),
+            },
+            Err(e) => {
+                let message = format!("{:#}", e);
+                ExecutionReport {
+                    failure: Some((GuestFailure::from_panic_message(&message), message)),
+                    segments: Vec::new(),
+                    journal: None,
+                }
+            }
+        };
+        Ok(report)
+    })
+    .await?
+}
+
+/// Build the prover backend selected by `prover.backend`
+pub async fn prover_from_config(
+    config: &ProverConfig,
+    input_storage: Option<Arc<dyn InputStorage>>,
+) -> Result<Arc<dyn Prover>> {
+    match config.backend {
+        ProverBackend::Boundless => {
+            let boundless = &config.boundless;
+            let rpc_url = boundless
+                .rpc_url
+                .as_ref()
+                .context("prover.boundless.rpc_url is not set")?
+                .expose()
+                .clone();
+            let private_key = boundless
+                .private_key
+                .as_ref()
+                .context("prover.boundless.private_key is not set")?
+                .expose()
+                .parse::<PrivateKeySigner>()
+                .context("Failed to parse private key")?;
+
+            let program_url = boundless
+                .program_url
+                .as_ref()
+                .context("prover.boundless.program_url is not set")?;
+            let program = VerifiedProgram::fetch(program_url, PAYMENT_CHECK_ID.into())
+                .await
+                .context("Failed to verify prover.boundless.program_url")?;
+            let input_storage =
+                input_storage.context("input storage is required for the boundless prover")?;
+
+            Ok(Arc::new(BoundlessProver::new(
+                rpc_url,
+                private_key,
+                program,
+                input_storage,
+                Duration::from_secs(boundless.poll_interval_secs),
+                boundless.offer.clone(),
+                boundless.retry.clone(),
+            )))
+        }
+        ProverBackend::Local => Ok(Arc::new(LocalProver)),
+        ProverBackend::Dev => Ok(Arc::new(DevModeProver)),
+    }
+}
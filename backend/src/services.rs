@@ -0,0 +1,152 @@
+use alloy_primitives::{Address, U256};
+use anyhow::{ensure, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::types::ServiceInfo;
+
+/// Web2 service that accepts fckuipaid logins, as configured in `[[services]]`
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Service {
+    /// Unique service identifier
+    pub id: u32,
+    /// Human-readable service name
+    pub name: String,
+    /// Description of the service
+    #[serde(default)]
+    pub description: String,
+    /// Logo shown by the frontend
+    #[serde(default)]
+    pub logo_url: Option<String>,
+    /// PaymentReceiver contract the service deployed
+    pub payment_receiver: Address,
+    /// Chain the PaymentReceiver contract is deployed on
+    pub chain_id: u64,
+    /// ERC-20 token the subscription is paid in
+    pub token: Address,
+    /// Monthly price in the token's smallest unit
+    pub price: U256,
+}
+
+impl From<&Service> for ServiceInfo {
+    fn from(service: &Service) -> Self {
+        Self {
+            id: service.id,
+            name: service.name.clone(),
+            description: service.description.clone(),
+            logo_url: service.logo_url.clone(),
+            payment_receiver: service.payment_receiver.to_string(),
+            chain_id: service.chain_id,
+            token: service.token.to_string(),
+            price: service.price.to_string(),
+        }
+    }
+}
+
+/// Services the backend proves and verifies payments for
+#[derive(Debug, Clone, Default)]
+pub struct ServiceRegistry {
+    services: Vec<Service>,
+}
+
+impl ServiceRegistry {
+    pub fn new(services: Vec<Service>) -> Result<Self> {
+        for (i, service) in services.iter().enumerate() {
+            for other in &services[..i] {
+                ensure!(
+                    other.id != service.id,
+                    "Duplicate service id {}",
+                    service.id
+                );
+                ensure!(
+                    (other.chain_id, other.payment_receiver)
+                        != (service.chain_id, service.payment_receiver),
+                    "Services {} and {} share PaymentReceiver {}",
+                    other.id,
+                    service.id,
+                    service.payment_receiver
+                );
+            }
+        }
+        Ok(Self { services })
+    }
+
+    /// Registered services, in configuration order
+    pub fn services(&self) -> &[Service] {
+        &self.services
+    }
+
+    /// Service that deployed the given PaymentReceiver contract
+    pub fn by_receiver(&self, chain_id: u64, payment_receiver: Address) -> Option<&Service> {
+        self.services
+            .iter()
+            .find(|s| s.chain_id == chain_id && s.payment_receiver == payment_receiver)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RECEIVER: &str = "0x6fEDEb0B4942A8b438AFE68ba7c8Af4637c41903";
+    const OTHER_RECEIVER: &str = "0x1111111111111111111111111111111111111111";
+
+    /// Parse a `[[services]]` entry the way the config file spells it
+    fn service(id: u32, chain_id: u64, payment_receiver: &str) -> Service {
+        toml::from_str(&format!(
+            "id = {}\nname = \"Service {}\"\npayment_receiver = \"{}\"\nchain_id = {}\ntoken = \"0x2F4AAF1b6700A61E176daF388e75D37B857a6695\"\nprice = \"1\"\n",
+            id, id, payment_receiver, chain_id
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn rejects_duplicate_ids() {
+        let err = ServiceRegistry::new(vec![
+            service(1, 421614, RECEIVER),
+            service(1, 421614, OTHER_RECEIVER),
+        ])
+        .unwrap_err();
+        assert!(err.to_string().contains("Duplicate service id 1"));
+    }
+
+    #[test]
+    fn rejects_shared_receivers() {
+        let err = ServiceRegistry::new(vec![
+            service(1, 421614, RECEIVER),
+            service(2, 421614, RECEIVER),
+        ])
+        .unwrap_err();
+        assert!(err.to_string().contains("Services 1 and 2 share"));
+
+        // The same address on another chain is a different contract
+        ServiceRegistry::new(vec![service(1, 421614, RECEIVER), service(2, 1, RECEIVER)]).unwrap();
+    }
+
+    #[test]
+    fn finds_services_by_chain_and_receiver() {
+        let registry = ServiceRegistry::new(vec![
+            service(1, 421614, RECEIVER),
+            service(2, 421614, OTHER_RECEIVER),
+            service(3, 1, RECEIVER),
+        ])
+        .unwrap();
+        let receiver: Address = RECEIVER.parse().unwrap();
+        let other: Address = OTHER_RECEIVER.parse().unwrap();
+
+        assert_eq!(registry.by_receiver(421614, receiver).unwrap().id, 1);
+        assert_eq!(registry.by_receiver(1, receiver).unwrap().id, 3);
+        assert!(registry.by_receiver(10, receiver).is_none());
+        assert!(registry.by_receiver(1, other).is_none());
+    }
+
+    #[test]
+    fn rejects_unknown_fields() {
+        let err = toml::from_str::<Service>(&format!(
+            "id = 1\nname = \"Test\"\npayment_receiver = \"{}\"\nchain_id = 1\ntoken = \"0x2F4AAF1b6700A61E176daF388e75D37B857a6695\"\nprice = \"1\"\nrecevier = \"typo\"\n",
+            RECEIVER
+        ))
+        .unwrap_err();
+        assert!(err.to_string().contains("unknown field `recevier`"));
+    }
+}
@@ -0,0 +1,303 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use tracing::info;
+use url::Url;
+
+use crate::config::{StorageBackend, StorageConfig};
+
+/// Boundless rejects inline request inputs larger than this many bytes
+pub const INLINE_INPUT_LIMIT: usize = 2048;
+
+/// Storage for guest inputs that are too large to be sent inline with a request
+#[async_trait]
+pub trait InputStorage: Send + Sync {
+    /// Name of the provider for logging
+    fn name(&self) -> &'static str;
+
+    /// Upload an encoded guest input and return a URL provers can fetch it from
+    async fn upload_input(&self, input: &[u8]) -> Result<Url>;
+}
+
+/// Upload an encoded guest input unless it is small enough to be sent inline,
+/// returning the URL provers fetch it from
+pub async fn upload_if_too_large(
+    storage: &dyn InputStorage,
+    encoded_input: &[u8],
+) -> Result<Option<Url>> {
+    if encoded_input.len() <= INLINE_INPUT_LIMIT {
+        info!("📦 Sending {} byte input inline", encoded_input.len());
+        return Ok(None);
+    }
+    info!(
+        "📤 Uploading {} byte input via {} storage",
+        encoded_input.len(),
+        storage.name()
+    );
+    let input_url = storage
+        .upload_input(encoded_input)
+        .await
+        .context("Failed to upload guest input")?;
+    Ok(Some(input_url))
+}
+
+/// Stores inputs on the local filesystem and serves them from the backend itself
+/// under `GET /inputs/{name}`
+pub struct LocalInputStorage {
+    /// Directory the inputs are written to
+    dir: PathBuf,
+    /// Publicly reachable base URL of this backend
+    public_base_url: Url,
+}
+
+impl LocalInputStorage {
+    pub fn new(dir: PathBuf, public_base_url: Url) -> Self {
+        Self {
+            dir,
+            public_base_url,
+        }
+    }
+
+    /// Read a previously stored input. Names are hex SHA-256 digests of the content.
+    pub async fn read(&self, name: &str) -> Result<Option<Vec<u8>>> {
+        if name.len() != 64 || !name.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Ok(None);
+        }
+
+        match tokio::fs::read(self.dir.join(name)).await {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).context("Failed to read stored input"),
+        }
+    }
+}
+
+#[async_trait]
+impl InputStorage for LocalInputStorage {
+    fn name(&self) -> &'static str {
+        "local"
+    }
+
+    async fn upload_input(&self, input: &[u8]) -> Result<Url> {
+        let name = hex::encode(Sha256::digest(input));
+
+        tokio::fs::create_dir_all(&self.dir)
+            .await
+            .context("Failed to create input storage directory")?;
+
+        // Write to a temporary file first so provers never fetch a partial input
+        let tmp_path = self.dir.join(format!("{}.tmp", name));
+        tokio::fs::write(&tmp_path, input)
+            .await
+            .context("Failed to write input file")?;
+        tokio::fs::rename(&tmp_path, self.dir.join(&name))
+            .await
+            .context("Failed to move input file into place")?;
+
+        let url = self
+            .public_base_url
+            .join(&format!("inputs/{}", name))
+            .context("Failed to build input URL")?;
+        info!("💾 Stored {} byte input at {}", input.len(), url);
+        Ok(url)
+    }
+}
+
+#[derive(Deserialize)]
+struct PinataResponse {
+    #[serde(rename = "IpfsHash")]
+    ipfs_hash: String,
+}
+
+/// Pins inputs to IPFS through a Pinata-compatible pinning API
+pub struct IpfsInputStorage {
+    client: reqwest::Client,
+    /// Pinning API endpoint
+    api_url: Url,
+    /// API token
+    jwt: String,
+    /// Gateway used to build the URL handed to provers
+    gateway_url: Url,
+}
+
+impl IpfsInputStorage {
+    pub fn new(api_url: Url, jwt: String, gateway_url: Url) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            api_url,
+            jwt,
+            gateway_url,
+        }
+    }
+}
+
+#[async_trait]
+impl InputStorage for IpfsInputStorage {
+    fn name(&self) -> &'static str {
+        "ipfs"
+    }
+
+    async fn upload_input(&self, input: &[u8]) -> Result<Url> {
+        let name = hex::encode(Sha256::digest(input));
+        let part = reqwest::multipart::Part::bytes(input.to_vec()).file_name(name);
+        let form = reqwest::multipart::Form::new().part("file", part);
+
+        let response = self
+            .client
+            .post(self.api_url.clone())
+            .bearer_auth(&self.jwt)
+            .multipart(form)
+            .send()
+            .await
+            .context("Failed to make HTTP request to IPFS pinning API")?;
+
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!(
+                "IPFS upload failed with status: {}",
+                response.status()
+            ));
+        }
+
+        let pinned: PinataResponse = response
+            .json()
+            .await
+            .context("Failed to parse IPFS pinning response")?;
+
+        let url = self
+            .gateway_url
+            .join(&format!("ipfs/{}", pinned.ipfs_hash))
+            .context("Failed to build input URL")?;
+        info!("📌 Pinned {} byte input to {}", input.len(), url);
+        Ok(url)
+    }
+}
+
+/// Input storage selected by `storage.backend`
+#[derive(Clone)]
+pub enum InputStorageBackend {
+    Local(Arc<LocalInputStorage>),
+    Ipfs(Arc<IpfsInputStorage>),
+}
+
+impl InputStorageBackend {
+    /// Configure the storage backend. Locally stored inputs are served under `public_base_url`.
+    pub fn from_config(config: &StorageConfig, public_base_url: Option<&Url>) -> Result<Self> {
+        match config.backend {
+            StorageBackend::Local => {
+                let public_base_url =
+                    public_base_url.context("server.public_base_url is not set")?;
+                Ok(Self::Local(Arc::new(LocalInputStorage::new(
+                    config.dir.clone(),
+                    public_base_url.clone(),
+                ))))
+            }
+            StorageBackend::Ipfs => {
+                let jwt = config
+                    .pinata_jwt
+                    .as_ref()
+                    .context("storage.pinata_jwt is not set")?;
+                Ok(Self::Ipfs(Arc::new(IpfsInputStorage::new(
+                    config.pinata_api_url.clone(),
+                    jwt.expose().to_string(),
+                    config.ipfs_gateway_url.clone(),
+                ))))
+            }
+        }
+    }
+
+    /// The provider used to upload inputs
+    pub fn provider(&self) -> Arc<dyn InputStorage> {
+        match self {
+            Self::Local(storage) => storage.clone(),
+            Self::Ipfs(storage) => storage.clone(),
+        }
+    }
+
+    /// The local provider, if inputs are served by this backend
+    pub fn local(&self) -> Option<Arc<LocalInputStorage>> {
+        match self {
+            Self::Local(storage) => Some(storage.clone()),
+            Self::Ipfs(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    /// Storage that counts uploads without storing anything
+    #[derive(Default)]
+    struct CountingStorage {
+        uploads: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl InputStorage for CountingStorage {
+        fn name(&self) -> &'static str {
+            "counting"
+        }
+
+        async fn upload_input(&self, _input: &[u8]) -> Result<Url> {
+            self.uploads.fetch_add(1, Ordering::Relaxed);
+            Ok(Url::parse("https://storage.example/input").unwrap())
+        }
+    }
+
+    fn local_storage() -> (LocalInputStorage, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("fckuipaid-inputs-{}", uuid::Uuid::new_v4()));
+        let base_url = Url::parse("https://backend.example/api/").unwrap();
+        (LocalInputStorage::new(dir.clone(), base_url), dir)
+    }
+
+    #[tokio::test]
+    async fn uploads_only_inputs_over_the_inline_limit() {
+        let storage = CountingStorage::default();
+
+        let inline = upload_if_too_large(&storage, &[0; INLINE_INPUT_LIMIT])
+            .await
+            .unwrap();
+        assert_eq!(inline, None);
+        assert_eq!(storage.uploads.load(Ordering::Relaxed), 0);
+
+        let uploaded = upload_if_too_large(&storage, &[0; INLINE_INPUT_LIMIT + 1])
+            .await
+            .unwrap();
+        assert_eq!(uploaded.unwrap().as_str(), "https://storage.example/input");
+        assert_eq!(storage.uploads.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn local_storage_serves_inputs_by_content_digest() {
+        let (storage, dir) = local_storage();
+        let input = b"guest input".to_vec();
+
+        let url = storage.upload_input(&input).await.unwrap();
+        let name = hex::encode(Sha256::digest(&input));
+        assert_eq!(
+            url.as_str(),
+            format!("https://backend.example/api/inputs/{}", name)
+        );
+        assert_eq!(std::fs::read(dir.join(&name)).unwrap(), input);
+        assert!(!dir.join(format!("{}.tmp", name)).exists());
+
+        assert_eq!(storage.read(&name).await.unwrap(), Some(input));
+        assert_eq!(storage.read(&"0".repeat(64)).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn local_storage_only_reads_digest_names() {
+        let (storage, dir) = local_storage();
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("secret"), b"not an input").unwrap();
+
+        assert_eq!(storage.read("secret").await.unwrap(), None);
+        assert_eq!(storage.read("../config.toml").await.unwrap(), None);
+    }
+}
@@ -1,3 +1,4 @@
+use fckuipaid_billing::BillingPeriod;
 use serde::{Deserialize, Serialize};
 
 /// Custom serializer for payment amounts to handle large numbers
@@ -28,10 +29,26 @@ pub struct GenerateProofRequest {
     pub user_address: String,
     /// Payment amount in wei
     pub payment_receiver: String,
-    /// Month of payment
-    pub month: u64,
-    /// Signature
+    /// Billing period of the payment, as `YYYY-MM` or on-chain month count
+    pub month: BillingPeriod,
+    /// EIP-712 signature of the login authorization (or of the legacy message)
     pub signature: String,
+    /// `personal_sign` signature of the nullifier key message, from which the guest
+    /// derives the user's nullifiers without committing it
+    pub nullifier_key: String,
+    /// Login challenge issued by the service (32-byte hex), bound into the proof
+    #[serde(default)]
+    pub challenge: Option<String>,
+    /// Nullifier epoch, defaults to the current epoch. The guest only accepts the epoch
+    /// containing the block the payment is checked against.
+    #[serde(default)]
+    pub epoch: Option<u64>,
+    /// Unix timestamp until which the signed login authorization is valid
+    #[serde(default)]
+    pub expiry: Option<u64>,
+    /// Signature is over the fixed legacy message instead of an EIP-712 authorization
+    #[serde(default)]
+    pub legacy_signature: bool,
 }
 
 /// Response containing the generated ZK proof
@@ -47,21 +64,148 @@ pub struct GenerateProofResponse {
     pub journal: String,
     /// Proof seal (ZK proof) as hex string
     pub seal: String,
-    /// User commitment hash as hex string
+    /// ABI-encoded Steel commitment (block hash and number) as hex string
     pub commitment: String,
+    /// Copy-pasteable `fkp1_` token bundling seal, journal and image ID
+    pub proof_token: String,
     /// Timestamp when proof was generated
     pub timestamp: u64,
 }
 
+/// Cycles of one segment of a dry run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SegmentCyclesInfo {
+    /// Segment size as power of two
+    pub po2: u32,
+    /// Cycles spent executing guest instructions
+    pub cycles: u32,
+}
+
+/// Result of executing the guest without proving
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DryRunResponse {
+    /// Whether all guest checks passed
+    pub success: bool,
+    /// Machine-readable code of the guest check that panicked
+    pub failure: Option<String>,
+    /// Panic message of the failed execution
+    pub panic_message: Option<String>,
+    /// Cycles spent executing guest instructions
+    pub user_cycles: u64,
+    /// Proven cycles including paging and segment padding, for sizing market offers
+    pub total_cycles: u64,
+    /// Cycles per segment
+    pub segments: Vec<SegmentCyclesInfo>,
+    /// Journal the proof would commit, as hex string
+    pub journal: Option<String>,
+}
+
+/// Lifecycle state of an asynchronous proof job
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    /// Preparing the guest input and the proof request
+    Preflighting,
+    /// Request submitted to the Boundless market
+    Submitted,
+    /// Request locked by a prover
+    Locked,
+    /// Proof being generated by a local or dev-mode prover
+    Proving,
+    /// Proof generated, result available
+    Fulfilled,
+    /// Request expired before it was fulfilled
+    Expired,
+    /// Proof generation failed
+    Failed,
+}
+
+/// Offer a Boundless request was submitted with
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OfferInfo {
+    /// Proven cycles the offer is priced for
+    pub cycles: u64,
+    /// Whether the cycles were measured by executing the guest, or estimated
+    pub cycles_measured: bool,
+    /// Price at the start of the ramp-up, in wei as decimal string
+    pub min_price: String,
+    /// Price at the end of the ramp-up, in wei as decimal string
+    pub max_price: String,
+    /// Ramp-up period in seconds
+    pub ramp_up_period: u32,
+    /// Seconds the locking prover has to fulfill the request
+    pub lock_timeout: u32,
+    /// Seconds until the request expires
+    pub timeout: u32,
+    /// Stake locked by the prover, as decimal string
+    pub lock_stake: String,
+}
+
+/// One attempt at proving a job
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AttemptInfo {
+    /// Prover backend of the attempt
+    pub prover: String,
+    /// Boundless request id as hex string, for market attempts
+    pub request_id: Option<String>,
+    /// Offer of the market request
+    pub offer: Option<OfferInfo>,
+    /// Why the attempt failed
+    pub error: Option<String>,
+}
+
+/// State of a proof job, returned on submission and when polling
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProofJobResponse {
+    /// Unique job identifier
+    pub job_id: String,
+    /// Current job state
+    pub status: JobStatus,
+    /// Boundless request id as hex string (once submitted)
+    pub request_id: Option<String>,
+    /// Offer of the Boundless request (once priced)
+    pub offer: Option<OfferInfo>,
+    /// Market submissions and fallbacks, oldest first
+    pub attempts: Vec<AttemptInfo>,
+    /// Generated proof (once fulfilled)
+    pub result: Option<GenerateProofResponse>,
+    /// Failure details (if expired or failed)
+    pub error: Option<String>,
+    /// Timestamp when the job was created
+    pub created_at: u64,
+    /// Timestamp of the last state change
+    pub updated_at: u64,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct VerifyProofRequest {
-    /// 🔮 The ZK proof to verify
-    pub proof: String,
-    /// 🔗 The public commitment
-    pub commitment: String,
+    /// 🎟️ Proof token (`fkp1_...`), used instead of `proof` and `commitment`
+    #[serde(default)]
+    pub proof_token: Option<String>,
+    /// 🔮 The ZK proof to verify (ABI-encoded seal as hex string)
+    #[serde(default)]
+    pub proof: Option<String>,
+    /// 🔗 The public commitment (proof journal as hex string)
+    #[serde(default)]
+    pub commitment: Option<String>,
+
+    /// 🏦 PaymentReceiver contract of the service the user is logging in to
+    pub payment_receiver: String,
+
+    /// 🎲 Challenge issued for this login session
+    pub challenge: String,
+
+    /// 📅 Billing period they're claiming payment for, as `YYYY-MM` or on-chain month count
+    pub month_year: BillingPeriod,
+}
 
-    /// 📅 Month/year they're claiming payment for
-    pub month_year: u64,
+/// Login challenge to be bound into a proof
+#[derive(Debug, Serialize)]
+pub struct ChallengeResponse {
+    /// Random 32-byte challenge as hex string
+    pub challenge: String,
+    /// Timestamp after which the challenge is no longer accepted
+    pub expires_at: u64,
 }
 
 /// Result of verifying a ZK proof - whether to grant access or not.
@@ -74,7 +218,29 @@ pub struct VerifyProofResponse {
     /// 🏷️ Which service was verified (if successful)
     pub verified_service: Option<String>,
     /// 📅 Which month/year was verified (if successful)
-    pub verified_month_year: Option<u64>,
+    pub verified_month_year: Option<BillingPeriod>,
+}
+
+impl VerifyProofResponse {
+    /// Access granted for the given service and billing period
+    pub fn granted(service: &str, month_year: BillingPeriod) -> Self {
+        Self {
+            access_granted: true,
+            reason: None,
+            verified_service: Some(service.to_string()),
+            verified_month_year: Some(month_year),
+        }
+    }
+
+    /// Access denied with a machine-readable reason
+    pub fn denied(reason: &str) -> Self {
+        Self {
+            access_granted: false,
+            reason: Some(reason.to_string()),
+            verified_service: None,
+            verified_month_year: None,
+        }
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -107,6 +273,36 @@ impl ErrorResponse {
         Self::new("Proof generation failed", "PROOF_ERROR", Some(details))
     }
 
+    /// Payment receiver that is not registered with this backend
+    pub fn unknown_service(payment_receiver: &str) -> Self {
+        Self::new(
+            "Unknown service",
+            "UNKNOWN_SERVICE",
+            Some(format!(
+                "No service is registered for payment receiver {}",
+                payment_receiver
+            )),
+        )
+    }
+
+    /// Idempotency key already used for a different request
+    pub fn idempotency_conflict() -> Self {
+        Self::new(
+            "Idempotency key reused",
+            "IDEMPOTENCY_KEY_REUSED",
+            Some("The Idempotency-Key was already used for a different proof request".to_string()),
+        )
+    }
+
+    /// Unknown proof job id
+    pub fn job_not_found(job_id: &str) -> Self {
+        Self::new(
+            "Proof job not found",
+            "JOB_NOT_FOUND",
+            Some(job_id.to_string()),
+        )
+    }
+
     /// Proof verification error (ZK proof verification failed)
     pub fn verification_error(details: String) -> Self {
         Self::new(
@@ -117,14 +313,23 @@ impl ErrorResponse {
     }
 }
 
+/// Service listed by `GET /services`
 #[derive(Debug, Serialize)]
 pub struct ServiceInfo {
-    /// Unique service identifier (1-6)
+    /// Unique service identifier
     pub id: u32,
     /// Human-readable service name
     pub name: String,
-    /// Minimum payment amount required (in wei)
-    pub min_amount: u64,
     /// Description of the service
     pub description: String,
-}
\ No newline at end of file
+    /// Logo shown by the frontend
+    pub logo_url: Option<String>,
+    /// PaymentReceiver contract to pay the subscription to
+    pub payment_receiver: String,
+    /// Chain the PaymentReceiver contract is deployed on
+    pub chain_id: u64,
+    /// ERC-20 token the subscription is paid in
+    pub token: String,
+    /// Monthly price in the token's smallest unit, as decimal string
+    pub price: String,
+}
@@ -0,0 +1,326 @@
+//! Billing periods of fckuipaid subscriptions.
+//!
+//! A subscription is paid per calendar month (UTC). On chain, the PaymentReceiver
+//! records payments by "unix time month count": the number of months since
+//! January 1970, so January 1970 is `0` and July 2025 is `666`.
+
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, TimeZone, Utc};
+
+/// Errors when constructing or parsing a billing period
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BillingPeriodError {
+    /// Month outside of 1..=12
+    InvalidMonth(u32),
+    /// Period before January 1970, which has no on-chain encoding
+    BeforeEpoch(i32),
+    /// Year after 9999
+    YearOutOfRange(i32),
+    /// On-chain value or timestamp too large to be a calendar month
+    OutOfRange(u64),
+    /// String is not of the form `YYYY-MM`
+    InvalidFormat(String),
+}
+
+impl fmt::Display for BillingPeriodError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidMonth(month) => write!(f, "invalid month {}", month),
+            Self::BeforeEpoch(year) => write!(f, "year {} is before 1970", year),
+            Self::YearOutOfRange(year) => write!(f, "year {} is out of range", year),
+            Self::OutOfRange(value) => write!(f, "{} is out of range", value),
+            Self::InvalidFormat(s) => write!(f, "invalid billing period '{}', expected YYYY-MM", s),
+        }
+    }
+}
+
+impl std::error::Error for BillingPeriodError {}
+
+/// Latest year a period may be in, keeping every period representable as a date
+const MAX_YEAR: i32 = 9999;
+
+/// A calendar month (UTC) a subscription is paid for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BillingPeriod {
+    year: i32,
+    month: u32,
+}
+
+impl BillingPeriod {
+    /// Period of the given year and month (1-12)
+    pub fn new(year: i32, month: u32) -> Result<Self, BillingPeriodError> {
+        if !(1..=12).contains(&month) {
+            return Err(BillingPeriodError::InvalidMonth(month));
+        }
+        if year < 1970 {
+            return Err(BillingPeriodError::BeforeEpoch(year));
+        }
+        if year > MAX_YEAR {
+            return Err(BillingPeriodError::YearOutOfRange(year));
+        }
+        Ok(Self { year, month })
+    }
+
+    pub fn year(&self) -> i32 {
+        self.year
+    }
+
+    /// Month of the year, 1-12
+    pub fn month(&self) -> u32 {
+        self.month
+    }
+
+    /// Period containing the given date
+    pub fn from_date(date: NaiveDate) -> Result<Self, BillingPeriodError> {
+        Self::new(date.year(), date.month())
+    }
+
+    /// Period containing the given point in time
+    pub fn containing(time: DateTime<Utc>) -> Result<Self, BillingPeriodError> {
+        Self::from_date(time.date_naive())
+    }
+
+    /// Period containing the given unix timestamp, e.g. of a block
+    pub fn from_timestamp(secs: u64) -> Result<Self, BillingPeriodError> {
+        let time = i64::try_from(secs)
+            .ok()
+            .and_then(|secs| DateTime::from_timestamp(secs, 0))
+            .ok_or(BillingPeriodError::OutOfRange(secs))?;
+        Self::containing(time)
+    }
+
+    /// Current period
+    #[cfg(feature = "clock")]
+    pub fn current() -> Self {
+        Self::containing(Utc::now()).expect("system clock is before 1970")
+    }
+
+    /// Decode the on-chain month count
+    pub fn from_onchain(value: u64) -> Result<Self, BillingPeriodError> {
+        let year = value / 12 + 1970;
+        if year > MAX_YEAR as u64 {
+            return Err(BillingPeriodError::OutOfRange(value));
+        }
+        Self::new(year as i32, (value % 12) as u32 + 1)
+    }
+
+    /// Encode as the on-chain month count
+    pub fn to_onchain(&self) -> u64 {
+        (self.year - 1970) as u64 * 12 + (self.month - 1) as u64
+    }
+
+    /// First day of the period
+    pub fn first_day(&self) -> NaiveDate {
+        NaiveDate::from_ymd_opt(self.year, self.month, 1).expect("valid billing period")
+    }
+
+    /// Start of the period (inclusive)
+    pub fn start(&self) -> DateTime<Utc> {
+        Utc.from_utc_datetime(&self.first_day().and_hms_opt(0, 0, 0).unwrap())
+    }
+
+    /// End of the period (exclusive), the start of the next one
+    pub fn end(&self) -> DateTime<Utc> {
+        let (year, month) = match self.month {
+            12 => (self.year + 1, 1),
+            month => (self.year, month + 1),
+        };
+        let first = NaiveDate::from_ymd_opt(year, month, 1).expect("valid billing period");
+        Utc.from_utc_datetime(&first.and_hms_opt(0, 0, 0).unwrap())
+    }
+
+    /// Period after this one
+    pub fn next(&self) -> Option<Self> {
+        Self::from_onchain(self.to_onchain() + 1).ok()
+    }
+
+    /// Period before this one
+    pub fn previous(&self) -> Option<Self> {
+        self.to_onchain()
+            .checked_sub(1)
+            .and_then(|value| Self::from_onchain(value).ok())
+    }
+
+    /// Whether a payment for this period grants access at `now`. Access is granted
+    /// from the start of the period until `grace` after its end, so users are not
+    /// locked out while the payment for the next month is pending.
+    pub fn is_current_at(&self, now: DateTime<Utc>, grace: std::time::Duration) -> bool {
+        let grace = Duration::from_std(grace).unwrap_or(Duration::MAX);
+        self.start() <= now
+            && self
+                .end()
+                .checked_add_signed(grace)
+                .is_none_or(|end| now < end)
+    }
+
+    /// [`Self::is_current_at`] for a unix timestamp, e.g. of a block
+    pub fn is_current_at_timestamp(&self, secs: u64, grace: std::time::Duration) -> bool {
+        i64::try_from(secs)
+            .ok()
+            .and_then(|secs| DateTime::from_timestamp(secs, 0))
+            .is_some_and(|time| self.is_current_at(time, grace))
+    }
+
+    /// [`Self::is_current_at`] for the current time
+    #[cfg(feature = "clock")]
+    pub fn is_current(&self, grace: std::time::Duration) -> bool {
+        self.is_current_at(Utc::now(), grace)
+    }
+}
+
+impl fmt::Display for BillingPeriod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}", self.year, self.month)
+    }
+}
+
+impl FromStr for BillingPeriod {
+    type Err = BillingPeriodError;
+
+    /// Parse a `YYYY-MM` period
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || BillingPeriodError::InvalidFormat(s.to_string());
+        let (year, month) = s.trim().split_once('-').ok_or_else(invalid)?;
+        if year.len() != 4 || month.len() != 2 {
+            return Err(invalid());
+        }
+        Self::new(
+            year.parse().map_err(|_| invalid())?,
+            month.parse().map_err(|_| invalid())?,
+        )
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impl {
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::BillingPeriod;
+
+    impl Serialize for BillingPeriod {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_str(self)
+        }
+    }
+
+    /// Latest year an on-chain month count is accepted for. Larger numbers are far more
+    /// likely a mistyped `MMYYYY` (`72025` decodes to year 7972) than a real period.
+    const MAX_ONCHAIN_YEAR: i32 = 2100;
+
+    /// Periods are given as `YYYY-MM` or as the on-chain month count
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Repr {
+        Str(String),
+        OnChain(u64),
+    }
+
+    impl<'de> Deserialize<'de> for BillingPeriod {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            match Repr::deserialize(deserializer)? {
+                Repr::Str(s) => s.parse().map_err(de::Error::custom),
+                Repr::OnChain(value) => match BillingPeriod::from_onchain(value) {
+                    Ok(period) if period.year() <= MAX_ONCHAIN_YEAR => Ok(period),
+                    _ => Err(de::Error::custom(format_args!(
+                        "month count {} is after {}, expected YYYY-MM or months since 1970",
+                        value, MAX_ONCHAIN_YEAR
+                    ))),
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn onchain_encoding_counts_months_since_1970() {
+        assert_eq!(BillingPeriod::new(1970, 1).unwrap().to_onchain(), 0);
+        assert_eq!(BillingPeriod::new(2025, 7).unwrap().to_onchain(), 666);
+        assert_eq!(
+            BillingPeriod::from_onchain(666).unwrap(),
+            BillingPeriod::new(2025, 7).unwrap()
+        );
+        for value in [0, 11, 12, 666, 1000] {
+            assert_eq!(
+                BillingPeriod::from_onchain(value).unwrap().to_onchain(),
+                value
+            );
+        }
+        assert!(BillingPeriod::from_onchain(u64::MAX).is_err());
+    }
+
+    #[test]
+    fn parses_and_displays_year_month() {
+        let period: BillingPeriod = "2025-07".parse().unwrap();
+        assert_eq!(period, BillingPeriod::new(2025, 7).unwrap());
+        assert_eq!(period.to_string(), "2025-07");
+        assert!("072025".parse::<BillingPeriod>().is_err());
+        assert!("2025-13".parse::<BillingPeriod>().is_err());
+        assert!("1969-12".parse::<BillingPeriod>().is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn deserializes_strings_and_plausible_month_counts() {
+        let parse = |json: &str| serde_json::from_str::<BillingPeriod>(json);
+        let july = BillingPeriod::new(2025, 7).unwrap();
+        assert_eq!(parse("\"2025-07\"").unwrap(), july);
+        assert_eq!(parse("666").unwrap(), july);
+        assert_eq!(serde_json::to_string(&july).unwrap(), "\"2025-07\"");
+
+        // December 2100 is the last accepted month count
+        assert_eq!(
+            parse("1571").unwrap(),
+            BillingPeriod::new(2100, 12).unwrap()
+        );
+        assert!(parse("1572").is_err());
+        // 07/2025 typed as a number would otherwise be July 7972
+        let err = parse("72025").unwrap_err();
+        assert!(err.to_string().contains("month count 72025"));
+    }
+
+    #[test]
+    fn converts_from_dates_and_timestamps() {
+        let date = NaiveDate::from_ymd_opt(2025, 12, 31).unwrap();
+        let period = BillingPeriod::from_date(date).unwrap();
+        assert_eq!(period, BillingPeriod::new(2025, 12).unwrap());
+        assert_eq!(
+            period.first_day(),
+            NaiveDate::from_ymd_opt(2025, 12, 1).unwrap()
+        );
+        assert_eq!(period.end(), BillingPeriod::new(2026, 1).unwrap().start());
+        assert_eq!(period.next(), BillingPeriod::new(2026, 1).ok());
+        assert_eq!(period.previous(), BillingPeriod::new(2025, 11).ok());
+
+        // 2025-07-01T00:00:00Z
+        assert_eq!(
+            BillingPeriod::from_timestamp(1751328000).unwrap(),
+            BillingPeriod::new(2025, 7).unwrap()
+        );
+        assert_eq!(
+            BillingPeriod::from_timestamp(1751327999).unwrap(),
+            BillingPeriod::new(2025, 6).unwrap()
+        );
+    }
+
+    #[test]
+    fn current_period_includes_grace_window() {
+        let period = BillingPeriod::new(2025, 7).unwrap();
+        let grace = std::time::Duration::from_secs(3 * 86400);
+        let at = |y, m, d| Utc.with_ymd_and_hms(y, m, d, 12, 0, 0).unwrap();
+
+        assert!(!period.is_current_at(at(2025, 6, 30), grace));
+        assert!(period.is_current_at(at(2025, 7, 15), grace));
+        assert!(period.is_current_at(at(2025, 8, 3), grace));
+        assert!(!period.is_current_at(at(2025, 8, 4), grace));
+
+        // 2025-07-01T00:00:00Z and one second before
+        assert!(period.is_current_at_timestamp(1751328000, grace));
+        assert!(!period.is_current_at_timestamp(1751327999, grace));
+    }
+}
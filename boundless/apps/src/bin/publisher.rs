@@ -12,48 +12,76 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-// This application demonstrates how to send an off-chain proof request
-// to the Bonsai proving service and publish the received proofs directly
-// to your deployed app contract.
+// This application proves a fckuipaid payment on the user's own machine, so that
+// neither the backend nor a prover market ever sees the user's address or signature.
+// It preflights the payment check against Arbitrum Sepolia, proves the guest locally
+// and outputs a `fkp1_` proof token to paste into the service's login page.
+// The `dry-run` subcommand only executes the guest, to report which check fails and how
+// many cycles a proof takes.
 
-use alloy_primitives::{Address, U256};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use alloy_primitives::{Address, Signature, B256};
 use anyhow::{ensure, Context, Result};
-use clap::Parser;
-use fckuipaid_methods::{PAYMENT_CHECK_ELF, PAYMENT_CHECK_ID};
-use hex;
-use revm_primitives::hardfork::SpecId;
-use risc0_ethereum_contracts::encode_seal;
-use risc0_steel::alloy::{
-    network::EthereumWallet,
-    providers::ProviderBuilder,
-    signers::local::PrivateKeySigner,
-    sol,
-    sol_types::{SolCall, SolValue},
+use clap::{Args, Parser, Subcommand};
+use fckuipaid_billing::BillingPeriod;
+use fckuipaid_chains::PAYMENT_CHAIN;
+use fckuipaid_methods::{GuestFailure, PAYMENT_CHECK_ELF, PAYMENT_CHECK_ID};
+use fckuipaid_verifier::{
+    is_low_s, legacy_signing_hash, unix_timestamp, Journal, LoginAuthorization, ProofToken,
+    NULLIFIER_KEY_MESSAGE,
 };
+use risc0_ethereum_contracts::encode_seal;
+use risc0_steel::alloy::{sol, sol_types::SolValue};
 use risc0_steel::{
-    config::ChainSpec, ethereum::EthEvmEnv, host::BlockNumberOrTag, Commitment, Contract,
+    ethereum::{EthEvmEnv, EthEvmInput},
+    host::BlockNumberOrTag,
+    Contract, EvmBlockHeader,
 };
-use risc0_zkvm::{default_prover, Digest, ExecutorEnv, ProverOpts, VerifierContext};
+use risc0_zkvm::{default_executor, default_prover, ExecutorEnv, ProverOpts, VerifierContext};
 use tokio::task;
 use tracing_subscriber::EnvFilter;
 use url::Url;
 
+/// Nullifier epoch length of the backend's default configuration
+const DEFAULT_EPOCH_SECS: u64 = 86400;
+
 sol! {
     /// Payment receiver payment check function signature.
     interface PaymentReceiver {
         function hasPaid(address userAddr, uint64 month) public view returns (bool);
     }
-
-    /// Data committed to by the guest.
-    struct Journal {
-        Commitment commitment;
-        uint64 month;
-    }
 }
 
-/// Simple program to create a proof to increment the Counter contract.
+/// Prove locally that you paid for a subscription and print a fckuipaid proof token.
 #[derive(Parser)]
-struct Args {
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Prove the payment and output the proof token
+    Prove(ProveArgs),
+    /// Execute the guest without proving and report cycle counts and the failed check
+    DryRun(PaymentArgs),
+}
+
+#[derive(Args)]
+struct ProveArgs {
+    #[command(flatten)]
+    payment: PaymentArgs,
+
+    /// Also write the proof token and decoded journal as JSON to this file
+    #[arg(long, short)]
+    output: Option<PathBuf>,
+}
+
+/// Inputs of the payment check guest
+#[derive(Args)]
+struct PaymentArgs {
     /// Arbitrum Sepolia RPC endpoint URL
     #[arg(long, env = "ARB_RPC_URL")]
     arb_rpc_url: Url,
@@ -70,29 +98,104 @@ struct Args {
     #[arg(long)]
     user_address: Address,
 
-    /// Month for which to query the payment
+    /// Billing period for which to query the payment, as YYYY-MM
     #[arg(long)]
-    month: u64,
+    month: BillingPeriod,
 
     /// Signature bytes as hex string
     #[arg(long)]
     signature: String,
+
+    /// Signature of the nullifier key message (`personal_sign`) as hex string
+    #[arg(long)]
+    nullifier_key: String,
+
+    /// Login challenge issued by the service, as 32-byte hex string
+    #[arg(long, default_value_t = B256::ZERO)]
+    challenge: B256,
+
+    /// Nullifier epoch requested by the service [default: epoch of the execution block]
+    #[arg(long)]
+    epoch: Option<u64>,
+
+    /// Length of the service's nullifier epochs in seconds
+    #[arg(
+        long,
+        default_value_t = DEFAULT_EPOCH_SECS,
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    epoch_secs: u64,
+
+    /// Unix timestamp until which the signed login authorization is valid
+    #[arg(long, required_unless_present = "legacy_signature")]
+    expiry: Option<u64>,
+
+    /// The signature is over the fixed legacy message instead of an EIP-712 authorization
+    #[arg(long)]
+    legacy_signature: bool,
+
+    /// How long after the end of the billing period the execution block may be
+    #[arg(long, default_value_t = 3 * 86400)]
+    grace_period_secs: u64,
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    // Initialize tracing. In order to view logs, run `RUST_LOG=info cargo run`
-    tracing_subscriber::fmt()
-        .with_env_filter(EnvFilter::from_default_env())
-        .init();
-    // Parse the command line arguments.
-    let args = Args::try_parse()?;
+impl PaymentArgs {
+    /// Epoch containing the execution block, the only one the guest accepts
+    fn block_epoch(&self, block_timestamp: u64) -> u64 {
+        block_timestamp / self.epoch_secs
+    }
+
+    fn epoch(&self, block_timestamp: u64) -> u64 {
+        self.epoch
+            .unwrap_or_else(|| self.block_epoch(block_timestamp))
+    }
+
+    /// Legacy signatures do not commit to an expiry
+    fn expiry(&self) -> u64 {
+        self.expiry.unwrap_or(0)
+    }
+}
+
+/// Recover the signer the same way the guest does, to fail before proving
+fn recover_signer(args: &PaymentArgs, signature: &[u8]) -> Result<Address> {
+    let signature = Signature::try_from(signature).context("invalid signature")?;
+    ensure!(
+        is_low_s(&signature),
+        "signature is not normalized to low s, the guest rejects it"
+    );
+    let signer = if args.legacy_signature {
+        signature.recover_address_from_msg(legacy_signing_hash())?
+    } else {
+        let authorization = LoginAuthorization {
+            paymentReceiver: args.payment_receiver,
+            chainId: PAYMENT_CHAIN.chain_id(),
+            month: args.month.to_onchain(),
+            expiry: args.expiry(),
+        };
+        signature.recover_address_from_prehash(&authorization.signing_hash())?
+    };
+    Ok(signer)
+}
+
+/// Recover the signer of the nullifier key the same way the guest does
+fn recover_nullifier_key_signer(nullifier_key: &[u8]) -> Result<Address> {
+    let nullifier_key = Signature::try_from(nullifier_key).context("invalid nullifier key")?;
+    ensure!(
+        is_low_s(&nullifier_key),
+        "nullifier key is not normalized to low s, the guest rejects it"
+    );
+    Ok(nullifier_key.recover_address_from_msg(NULLIFIER_KEY_MESSAGE)?)
+}
 
+/// Preflight the `hasPaid` call, returning the guest's EVM input, the result of the call
+/// and the timestamp of the execution block
+async fn preflight(args: &PaymentArgs) -> Result<(EthEvmInput, bool, u64)> {
     // Create Arbitrum Sepolia chain spec
-    let arb_sepolia_chain_spec = ChainSpec::new_single(421614, SpecId::CANCUN);
+    let arb_sepolia_chain_spec = PAYMENT_CHAIN.chain_spec();
 
     let mut env = EthEvmEnv::builder()
-        .rpc(args.arb_rpc_url)
+        .rpc(args.arb_rpc_url.clone())
+        .block_number_or_tag(args.execution_block)
         .chain_spec(&arb_sepolia_chain_spec)
         .build()
         .await?;
@@ -100,35 +203,176 @@ async fn main() -> Result<()> {
     // Prepare the function call
     let call = PaymentReceiver::hasPaidCall {
         userAddr: args.user_address,
-        month: args.month,
+        month: args.month.to_onchain(),
     };
 
     // Preflight the call to prepare the input that is required to execute the function in
     // the guest without RPC access. It also returns the result of the call.
     let mut contract = Contract::preflight(args.payment_receiver, &mut env);
     let returns = contract.call_builder(&call).call().await?;
-    assert!(returns == true);
+
+    let block_timestamp = env.header().timestamp();
 
     // Finally, construct the input from the environment.
-    // There are two options: Use EIP-4788 for verification by providing a Beacon API endpoint,
-    // or use the regular `blockhash' opcode.
     let evm_input = env.into_input().await?;
 
+    Ok((evm_input, returns, block_timestamp))
+}
+
+/// Build the executor environment with the guest inputs in the order the guest reads them
+fn executor_env(
+    args: &PaymentArgs,
+    evm_input: &EthEvmInput,
+    signature_bytes: &[u8],
+    nullifier_key: &[u8],
+    epoch: u64,
+) -> Result<ExecutorEnv<'static>> {
+    ExecutorEnv::builder()
+        .write(evm_input)?
+        .write(&args.payment_receiver)?
+        .write(&args.month.to_onchain())?
+        .write(&args.user_address)?
+        .write(&signature_bytes)?
+        .write(&nullifier_key)?
+        .write(&args.challenge)?
+        .write(&epoch)?
+        .write(&args.epoch_secs)?
+        .write(&args.expiry())?
+        .write(&args.legacy_signature)?
+        .write(&args.grace_period_secs)?
+        .build()
+}
+
+/// Decode the hex signature argument
+fn decode_signature(args: &PaymentArgs) -> Result<Vec<u8>> {
+    hex::decode(args.signature.trim_start_matches("0x")).context("invalid hex signature")
+}
+
+/// Decode the hex nullifier key argument
+fn decode_nullifier_key(args: &PaymentArgs) -> Result<Vec<u8>> {
+    hex::decode(args.nullifier_key.trim_start_matches("0x")).context("invalid hex nullifier key")
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    // Initialize tracing. In order to view logs, run `RUST_LOG=info cargo run`
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .with_writer(std::io::stderr)
+        .init();
+    // Parse the command line arguments.
+    let cli = Cli::try_parse()?;
+
+    match cli.command {
+        Command::Prove(args) => prove(args).await,
+        Command::DryRun(args) => dry_run(args).await,
+    }
+}
+
+/// Execute the guest without proving, to debug failing inputs and size market offers
+async fn dry_run(args: PaymentArgs) -> Result<()> {
+    let signature_bytes = decode_signature(&args)?;
+    let nullifier_key = decode_nullifier_key(&args)?;
+    // The checks are left to the guest, so that the dry run reports which one fails
+    let (evm_input, returns, block_timestamp) = preflight(&args).await?;
+    log::info!("Preflight: hasPaid = {}", returns);
+
+    let env = executor_env(
+        &args,
+        &evm_input,
+        &signature_bytes,
+        &nullifier_key,
+        args.epoch(block_timestamp),
+    )?;
+    let result =
+        task::spawn_blocking(move || default_executor().execute(env, PAYMENT_CHECK_ELF)).await?;
+    let session = match result {
+        Ok(session) => session,
+        Err(e) => {
+            let message = format!("{:#}", e);
+            let failure = GuestFailure::from_panic_message(&message);
+            eprintln!("Check failed:     {} ({})", failure, failure.reason());
+            eprintln!("Panic message:    {}", message);
+            anyhow::bail!("guest execution failed: {}", failure);
+        }
+    };
+
+    let user_cycles: u64 = session.segments.iter().map(|s| s.cycles as u64).sum();
+    let total_cycles: u64 = session.segments.iter().map(|s| 1u64 << s.po2).sum();
+    for (index, segment) in session.segments.iter().enumerate() {
+        eprintln!(
+            "Segment {:>3}:      {} cycles (po2 {})",
+            index, segment.cycles, segment.po2
+        );
+    }
+    eprintln!("Segments:         {}", session.segments.len());
+    eprintln!("User cycles:      {}", user_cycles);
+    // Prover market offers are priced by the proven cycles, including paging and padding
+    eprintln!("Total cycles:     {}", total_cycles);
+
+    let journal = Journal::abi_decode(&session.journal.bytes).context("invalid journal")?;
+    eprintln!("Block timestamp:  {}", journal.blockTimestamp);
+    eprintln!("Nullifier:        {}", journal.nullifier);
+    println!("All checks passed");
+
+    Ok(())
+}
+
+/// Prove the payment locally and output the proof token
+async fn prove(args: ProveArgs) -> Result<()> {
+    let output = args.output;
+    let args = args.payment;
+
+    // Decode hex signature string to bytes
+    let signature_bytes = decode_signature(&args)?;
+    let signer = recover_signer(&args, &signature_bytes)?;
+    ensure!(
+        signer == args.user_address,
+        "signature was made by {}, not by {}",
+        signer,
+        args.user_address
+    );
+    let nullifier_key = decode_nullifier_key(&args)?;
+    let key_signer = recover_nullifier_key_signer(&nullifier_key)?;
+    ensure!(
+        key_signer == args.user_address,
+        "nullifier key was signed by {}, not by {}",
+        key_signer,
+        args.user_address
+    );
+    // The guest checks the period against the execution block, fail before proving
+    ensure!(
+        args.month.is_current_at_timestamp(
+            unix_timestamp(),
+            Duration::from_secs(args.grace_period_secs)
+        ),
+        "billing period {} is not current",
+        args.month
+    );
+
+    let (evm_input, returns, block_timestamp) = preflight(&args).await?;
+    ensure!(
+        returns,
+        "{} has not paid {} for {}",
+        args.user_address,
+        args.payment_receiver,
+        args.month
+    );
+    let epoch = args.epoch(block_timestamp);
+    ensure!(
+        epoch == args.block_epoch(block_timestamp),
+        "epoch {} does not contain the execution block",
+        epoch
+    );
+
+    log::info!(
+        "Proving payment for {} locally, this may take a while",
+        args.month
+    );
+
     // Create the steel proof.
+    let env = executor_env(&args, &evm_input, &signature_bytes, &nullifier_key, epoch)?;
     let prove_info = task::spawn_blocking(move || {
-        // Decode hex signature string to bytes
-        let signature_bytes =
-            hex::decode(&args.signature.trim_start_matches("0x")).expect("Invalid hex signature");
-
-        let env = ExecutorEnv::builder()
-            .write(&evm_input)?
-            .write(&args.payment_receiver)?
-            .write(&args.month)?
-            .write(&args.user_address)?
-            .write(&signature_bytes)?
-            .build()
-            .unwrap();
-
         default_prover().prove_with_ctx(
             env,
             &VerifierContext::default(),
@@ -139,14 +383,62 @@ async fn main() -> Result<()> {
     .await?
     .context("failed to create proof")?;
     let receipt = prove_info.receipt;
-    let journal = &receipt.journal.bytes;
 
-    // Decode and log the commitment
-    let journal = Journal::abi_decode(journal).context("invalid journal")?;
+    // Decode the journal
+    let journal = Journal::abi_decode(&receipt.journal.bytes).context("invalid journal")?;
+    ensure!(
+        journal.paymentContract == args.payment_receiver,
+        "journal is for payment receiver {}",
+        journal.paymentContract
+    );
     log::debug!("Steel commitment: {:?}", journal.commitment);
 
-    // ABI encode the seal.
+    // ABI encode the seal and bundle it into a proof token.
     let seal = encode_seal(&receipt).context("invalid receipt")?;
+    let token = ProofToken::new(PAYMENT_CHECK_ID, seal, receipt.journal.bytes.clone()).encode();
+
+    let (block, _) = journal.commitment.decode_id();
+    eprintln!("Payment receiver: {}", journal.paymentContract);
+    eprintln!("Chain ID:         {}", journal.chainId);
+    eprintln!("Billing period:   {}", args.month);
+    eprintln!(
+        "Block:            {} ({})",
+        block, journal.commitment.digest
+    );
+    eprintln!("Block timestamp:  {}", journal.blockTimestamp);
+    eprintln!("Challenge:        {}", journal.challenge);
+    eprintln!("Nullifier:        {}", journal.nullifier);
+    eprintln!("Epoch:            {}", journal.epoch);
+
+    if let Some(path) = &output {
+        let output = serde_json::json!({
+            "proof_token": token,
+            "journal": {
+                "commitment": {
+                    "block": block.to_string(),
+                    "digest": journal.commitment.digest,
+                    "config_id": journal.commitment.configID,
+                },
+                "payment_contract": journal.paymentContract,
+                "chain_id": journal.chainId,
+                "month": args.month.to_string(),
+                "challenge": journal.challenge,
+                "nullifier": journal.nullifier,
+                "epoch": journal.epoch,
+                "epoch_length": journal.epochLength,
+                "expiry": journal.expiry,
+                "legacy_signature": journal.legacySignature,
+                "block_timestamp": journal.blockTimestamp,
+                "grace_period": journal.gracePeriod,
+            },
+        });
+        std::fs::write(path, serde_json::to_string_pretty(&output)?)
+            .with_context(|| format!("failed to write {}", path.display()))?;
+        eprintln!("Wrote proof token and journal to {}", path.display());
+    }
+
+    // The token goes to stdout so it can be piped or copied on its own
+    println!("{}", token);
 
     Ok(())
 }
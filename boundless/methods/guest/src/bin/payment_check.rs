@@ -15,11 +15,17 @@
 #![allow(unused_doc_comments)]
 #![no_main]
 
-use alloy_primitives::{keccak256, Address, Signature};
+use alloy_primitives::{Address, Signature, B256};
 use alloy_sol_types::{sol, SolValue};
-use revm_primitives::hardfork::SpecId;
-use risc0_steel::{config::ChainSpec, ethereum::EthEvmInput, Commitment, Contract};
+use fckuipaid_billing::BillingPeriod;
+use fckuipaid_chains::PAYMENT_CHAIN;
+use fckuipaid_protocol::{
+    derive_nullifier, is_low_s, legacy_signing_hash, Commitment, Journal, LoginAuthorization,
+    NULLIFIER_KEY_MESSAGE,
+};
+use risc0_steel::{ethereum::EthEvmInput, Contract, EvmBlockHeader};
 use risc0_zkvm::guest::env;
+use std::time::Duration;
 
 risc0_zkvm::guest::entry!(main);
 
@@ -32,13 +38,8 @@ sol! {
     }
 }
 
-/// ABI encodable journal data.
-sol! {
-    struct Journal {
-        Commitment commitment;
-        uint64 month;
-    }
-}
+/// Chain ID of Arbitrum Sepolia, where the PaymentReceiver contracts are deployed
+const ARB_SEPOLIA_CHAIN_ID: u64 = PAYMENT_CHAIN.chain_id();
 
 fn main() {
     // Read the input from the guest environment.
@@ -47,16 +48,40 @@ fn main() {
     let month: u64 = env::read();
     let user_address: Address = env::read();
     let signature_bytes: Vec<u8> = env::read();
+    let nullifier_key_bytes: Vec<u8> = env::read();
+    let challenge: B256 = env::read();
+    let epoch: u64 = env::read();
+    let epoch_length: u64 = env::read();
+    let expiry: u64 = env::read();
+    let legacy_signature: bool = env::read();
+    let grace_period: u64 = env::read();
 
-    let message = b"This message proofs your ownership of your address in zk proof";
+    // The month must be a valid on-chain billing period. The panic messages of the checks
+    // are recognized by `fckuipaid_methods::GuestFailure`, keep them in sync.
+    let period = BillingPeriod::from_onchain(month).expect("invalid billing period");
 
     // Create the Arbitrum Sepolia chain spec
-    let arb_sepolia_chain_spec = ChainSpec::new_single(421614, SpecId::CANCUN);
+    let arb_sepolia_chain_spec = PAYMENT_CHAIN.chain_spec();
 
     // Converts the input into a `EvmEnv` for execution. It checks that the state matches the state
     // root in the header provided in the input.
     let env = input.into_env(&arb_sepolia_chain_spec);
 
+    // Check that the claimed month is the month of the block the payment is checked
+    // against, or the month before it within the grace period
+    let block_timestamp = env.header().timestamp();
+    assert!(
+        period.is_current_at_timestamp(block_timestamp, Duration::from_secs(grace_period)),
+        "billing period is not current at the block timestamp"
+    );
+
+    // The nullifier epoch must be the one containing the block, so that a user cannot
+    // present nullifiers of arbitrary epochs to multiply their logins
+    assert!(
+        epoch_length > 0 && epoch == block_timestamp / epoch_length,
+        "nullifier epoch does not contain the block timestamp"
+    );
+
     // Execute the view call; it returns the result in the type generated by the `sol!` macro.
     let call = PaymentReceiver::hasPaidCall {
         userAddr: user_address,
@@ -67,22 +92,79 @@ fn main() {
         .call();
 
     // Check that the user has paid for the given month
-    assert!(returns);
+    assert!(returns, "user has not paid for the billing period");
 
     // Check that the given signature is a valid signature for the provided address
-    let signature = Signature::try_from(signature_bytes.as_slice()).unwrap();
-    // Ethereum signed message prefix
-    let prefix = format!("\x19Ethereum Signed Message:\n{}", message.len());
-    let eth_message_hash = keccak256([prefix.as_bytes(), message].concat());
-    let recovered_address = signature
-        .recover_address_from_msg(&eth_message_hash)
-        .expect("recovery failed");
-    assert_eq!(recovered_address, user_address);
-
-    // Commit the block hash and number used when deriving `view_call_env` to the journal.
+    let signature = Signature::try_from(signature_bytes.as_slice()).expect("invalid signature");
+    // A signature with a larger `s` is the malleable twin of a low-`s` signature of the
+    // same message; wallets only produce low-`s` ones.
+    assert!(is_low_s(&signature), "signature is not normalized to low s");
+    let recovered_address = if legacy_signature {
+        // Legacy mode: the user signed a fixed message, which is not bound to the service
+        signature
+            .recover_address_from_msg(legacy_signing_hash())
+            .expect("signature recovery failed")
+    } else {
+        // The user signed an EIP-712 authorization for this service, month and expiry
+        let authorization = LoginAuthorization {
+            paymentReceiver: payment_contract,
+            chainId: ARB_SEPOLIA_CHAIN_ID,
+            month,
+            expiry,
+        };
+        signature
+            .recover_address_from_prehash(&authorization.signing_hash())
+            .expect("signature recovery failed")
+    };
+    assert_eq!(
+        recovered_address, user_address,
+        "signature was not made by the user"
+    );
+
+    // Derive the nullifier from the user's nullifier key, a signature of a fixed message
+    // that only the user can produce. It is not committed, so services cannot link the
+    // nullifiers of one user; a high-`s` twin would yield a second nullifier.
+    let nullifier_key =
+        Signature::try_from(nullifier_key_bytes.as_slice()).expect("invalid nullifier key");
+    assert!(
+        is_low_s(&nullifier_key),
+        "nullifier key is not normalized to low s"
+    );
+    let key_signer = nullifier_key
+        .recover_address_from_msg(NULLIFIER_KEY_MESSAGE)
+        .expect("nullifier key recovery failed");
+    assert_eq!(
+        key_signer, user_address,
+        "nullifier key was not signed by the user"
+    );
+    let nullifier = derive_nullifier(
+        &nullifier_key,
+        payment_contract,
+        ARB_SEPOLIA_CHAIN_ID,
+        epoch,
+    );
+
+    // Commit the block hash and number used when deriving `view_call_env` to the journal,
+    // together with the contract and chain the payment was checked on and the service-issued
+    // login challenge.
+    let commitment = env.into_commitment();
     let journal = Journal {
-        commitment: env.into_commitment(),
+        commitment: Commitment {
+            id: commitment.id,
+            digest: commitment.digest,
+            configID: commitment.configID,
+        },
+        paymentContract: payment_contract,
+        chainId: ARB_SEPOLIA_CHAIN_ID,
         month: month,
+        challenge,
+        nullifier,
+        epoch,
+        epochLength: epoch_length,
+        expiry,
+        legacySignature: legacy_signature,
+        blockTimestamp: block_timestamp,
+        gracePeriod: grace_period,
     };
     env::commit_slice(&journal.abi_encode());
-}
\ No newline at end of file
+}
@@ -0,0 +1,153 @@
+// Copyright 2025 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt;
+
+/// Check of the payment check guest that failed, recognized by its panic message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GuestFailure {
+    /// The month is not a valid on-chain billing period
+    InvalidPeriod,
+    /// The billing period is not current at the timestamp of the execution block
+    PeriodNotCurrent,
+    /// `hasPaid` returned false for the user and billing period
+    NotPaid,
+    /// The signature is malformed, malleable or was not made by the user
+    BadSignature,
+    /// The nullifier key is malformed, malleable or was not signed by the user
+    BadNullifierKey,
+    /// The nullifier epoch is not the one containing the execution block
+    EpochMismatch,
+    /// Any other panic, e.g. an EVM input that does not match its state root
+    Other,
+}
+
+/// Panic messages of the guest's checks. These must stay in sync with the
+/// `expect` and `assert` messages in `payment_check.rs`.
+const PANIC_MESSAGES: &[(&str, GuestFailure)] = &[
+    ("invalid billing period", GuestFailure::InvalidPeriod),
+    (
+        "billing period is not current",
+        GuestFailure::PeriodNotCurrent,
+    ),
+    ("user has not paid", GuestFailure::NotPaid),
+    ("invalid signature", GuestFailure::BadSignature),
+    (
+        "signature is not normalized to low s",
+        GuestFailure::BadSignature,
+    ),
+    ("signature recovery failed", GuestFailure::BadSignature),
+    (
+        "signature was not made by the user",
+        GuestFailure::BadSignature,
+    ),
+    ("invalid nullifier key", GuestFailure::BadNullifierKey),
+    (
+        "nullifier key is not normalized to low s",
+        GuestFailure::BadNullifierKey,
+    ),
+    (
+        "nullifier key recovery failed",
+        GuestFailure::BadNullifierKey,
+    ),
+    (
+        "nullifier key was not signed by the user",
+        GuestFailure::BadNullifierKey,
+    ),
+    (
+        "nullifier epoch does not contain the block timestamp",
+        GuestFailure::EpochMismatch,
+    ),
+];
+
+impl GuestFailure {
+    /// Classify the error of a failed guest execution by the panic message it contains
+    pub fn from_panic_message(message: &str) -> Self {
+        PANIC_MESSAGES
+            .iter()
+            .find(|(panic, _)| message.contains(panic))
+            .map(|(_, failure)| *failure)
+            .unwrap_or(Self::Other)
+    }
+
+    /// Machine-readable failure code, matching the backend's preflight error codes
+    pub fn reason(&self) -> &'static str {
+        match self {
+            Self::InvalidPeriod => "INVALID_PERIOD",
+            Self::PeriodNotCurrent => "PERIOD_NOT_CURRENT",
+            Self::NotPaid => "NOT_PAID",
+            Self::BadSignature => "BAD_SIGNATURE",
+            Self::BadNullifierKey => "BAD_NULLIFIER_KEY",
+            Self::EpochMismatch => "EPOCH_MISMATCH",
+            Self::Other => "GUEST_PANIC",
+        }
+    }
+}
+
+impl fmt::Display for GuestFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::InvalidPeriod => "invalid billing period",
+            Self::PeriodNotCurrent => "billing period not current at the execution block",
+            Self::NotPaid => "user has not paid",
+            Self::BadSignature => "bad signature",
+            Self::BadNullifierKey => "bad nullifier key",
+            Self::EpochMismatch => "nullifier epoch not of the execution block",
+            Self::Other => "guest panicked",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_executor_errors() {
+        assert_eq!(
+            GuestFailure::from_panic_message(
+                "Guest panicked: user has not paid for the billing period"
+            ),
+            GuestFailure::NotPaid
+        );
+        assert_eq!(
+            GuestFailure::from_panic_message(
+                "Guest panicked: assertion `left == right` failed: signature was not made by the user"
+            ),
+            GuestFailure::BadSignature
+        );
+        assert_eq!(
+            GuestFailure::from_panic_message(
+                "Guest panicked: billing period is not current at the block timestamp"
+            ),
+            GuestFailure::PeriodNotCurrent
+        );
+        assert_eq!(
+            GuestFailure::from_panic_message(
+                "Guest panicked: assertion `left == right` failed: nullifier key was not signed by the user"
+            ),
+            GuestFailure::BadNullifierKey
+        );
+        assert_eq!(
+            GuestFailure::from_panic_message(
+                "Guest panicked: nullifier epoch does not contain the block timestamp"
+            ),
+            GuestFailure::EpochMismatch
+        );
+        assert_eq!(
+            GuestFailure::from_panic_message("Guest panicked: state root mismatch"),
+            GuestFailure::Other
+        );
+    }
+}
@@ -14,3 +14,7 @@
 
 //! Generated crate containing the image ID and ELF binary of the build guest.
 include!(concat!(env!("OUT_DIR"), "/methods.rs"));
+
+mod failure;
+
+pub use failure::GuestFailure;
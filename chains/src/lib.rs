@@ -0,0 +1,176 @@
+//! EVM chain specs shared by the fckuipaid host, guest and publisher.
+//!
+//! Every component builds its Steel chain spec with [`NamedChain::chain_spec`], so the
+//! preflight on the host and the execution in the guest always run the payment check with
+//! the same rules, and verifiers expect the [`NamedChain::config_id`] the guest commits to.
+
+use std::fmt;
+use std::str::FromStr;
+
+use alloy_primitives::B256;
+use revm_primitives::hardfork::SpecId;
+use risc0_steel::config::ChainSpec;
+
+/// Chain the PaymentReceiver contracts are deployed on and the payment check guest is built for
+pub const PAYMENT_CHAIN: NamedChain = NamedChain::ArbitrumSepolia;
+
+/// EVM chains known to fckuipaid
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum NamedChain {
+    EthereumMainnet,
+    EthereumSepolia,
+    ArbitrumOne,
+    ArbitrumSepolia,
+    Base,
+}
+
+impl NamedChain {
+    /// All known chains
+    pub const ALL: [NamedChain; 5] = [
+        Self::EthereumMainnet,
+        Self::EthereumSepolia,
+        Self::ArbitrumOne,
+        Self::ArbitrumSepolia,
+        Self::Base,
+    ];
+
+    /// EIP-155 chain ID
+    pub const fn chain_id(self) -> u64 {
+        match self {
+            Self::EthereumMainnet => 1,
+            Self::EthereumSepolia => 11155111,
+            Self::ArbitrumOne => 42161,
+            Self::ArbitrumSepolia => 421614,
+            Self::Base => 8453,
+        }
+    }
+
+    /// EVM spec the chain currently executes with
+    pub const fn spec_id(self) -> SpecId {
+        match self {
+            Self::EthereumMainnet | Self::EthereumSepolia => SpecId::PRAGUE,
+            // ArbOS and the OP stack follow Ethereum's execution rules up to Cancun
+            Self::ArbitrumOne | Self::ArbitrumSepolia | Self::Base => SpecId::CANCUN,
+        }
+    }
+
+    /// Steel chain spec to preflight and execute calls on the chain with
+    pub fn chain_spec(self) -> ChainSpec<SpecId> {
+        ChainSpec::new_single(self.chain_id(), self.spec_id())
+    }
+
+    /// Digest of the chain spec, which Steel commits to as the commitment's config ID
+    pub fn config_id(self) -> B256 {
+        B256::from_slice(self.chain_spec().digest().as_bytes())
+    }
+
+    /// Kebab-case name, as used in config files
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::EthereumMainnet => "ethereum-mainnet",
+            Self::EthereumSepolia => "ethereum-sepolia",
+            Self::ArbitrumOne => "arbitrum-one",
+            Self::ArbitrumSepolia => "arbitrum-sepolia",
+            Self::Base => "base",
+        }
+    }
+
+    /// The known chain with the given chain ID
+    pub fn from_chain_id(chain_id: u64) -> Option<Self> {
+        Self::ALL.into_iter().find(|c| c.chain_id() == chain_id)
+    }
+}
+
+impl fmt::Display for NamedChain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Error parsing an unknown chain name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownChain(pub String);
+
+impl fmt::Display for UnknownChain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown chain '{}'", self.0)
+    }
+}
+
+impl std::error::Error for UnknownChain {}
+
+impl FromStr for NamedChain {
+    type Err = UnknownChain;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|c| c.name() == s)
+            .ok_or_else(|| UnknownChain(s.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chain_ids_are_unique() {
+        for (i, a) in NamedChain::ALL.iter().enumerate() {
+            for b in &NamedChain::ALL[i + 1..] {
+                assert_ne!(a.chain_id(), b.chain_id(), "{} and {}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn chain_id_and_name_resolve_identically() {
+        for chain in NamedChain::ALL {
+            let by_id = NamedChain::from_chain_id(chain.chain_id()).unwrap();
+            let by_name: NamedChain = chain.name().parse().unwrap();
+            assert_eq!(by_id, chain);
+            assert_eq!(by_name, chain);
+            assert_eq!(by_id.spec_id(), by_name.spec_id());
+        }
+    }
+
+    #[test]
+    fn payment_chain_is_arbitrum_sepolia_cancun() {
+        // The guest, the publisher and the backend all preflight or execute against this spec
+        assert_eq!(PAYMENT_CHAIN.chain_id(), 421614);
+        assert_eq!(PAYMENT_CHAIN.spec_id(), SpecId::CANCUN);
+        assert_eq!(NamedChain::from_chain_id(421614), Some(PAYMENT_CHAIN));
+    }
+
+    #[test]
+    fn host_and_guest_specs_share_the_config_id() {
+        // The guest commits the digest of the spec it executes with, built by the same
+        // `chain_spec()` the backend preflights with; the config ID must not depend on
+        // where the spec is built
+        let guest_spec = ChainSpec::new_single(PAYMENT_CHAIN.chain_id(), SpecId::CANCUN);
+        assert_eq!(
+            PAYMENT_CHAIN.config_id(),
+            B256::from_slice(guest_spec.digest().as_bytes())
+        );
+
+        for (i, a) in NamedChain::ALL.iter().enumerate() {
+            for b in &NamedChain::ALL[i + 1..] {
+                assert_ne!(a.config_id(), b.config_id(), "{} and {}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn unknown_chains_are_rejected() {
+        assert_eq!(NamedChain::from_chain_id(0), None);
+        assert_eq!(
+            "arbitrum".parse::<NamedChain>(),
+            Err(UnknownChain("arbitrum".to_string()))
+        );
+    }
+}
@@ -19,6 +19,7 @@ import Vfp from "../../assets/vfp.svg";
 import {
   useAccount,
   useWriteContract,
+  useSignTypedData,
   useSignMessage,
 } from "wagmi";
 import {
@@ -26,18 +27,45 @@ import {
   SMARTCONTRACTABI,
   ERC20ADDRESS,
   SMARTCONTRACTADDRESS,
-  zkKey
 } from "./contract.js";
 import { parseUnits } from "viem";
 
+// On-chain billing period: months since January 1970
+const billingPeriod = (month, year) =>
+  BigInt((Number(year) - 1970) * 12 + Number(month) - 1);
+
+const BACKEND_URL =
+  import.meta.env.VITE_BACKEND_URL ?? "http://localhost:3001";
+
+// Must match NULLIFIER_KEY_MESSAGE in the protocol crate
+const NULLIFIER_KEY_MESSAGE =
+  "Derive my fckuipaid nullifier key (v1). Only sign this on fckuipaid.";
+
+const sleep = (ms) => new Promise((resolve) => setTimeout(resolve, ms));
+
+// Poll a proof job until it is fulfilled, expired or failed
+const waitForProof = async (jobId) => {
+  for (;;) {
+    const res = await fetch(`${BACKEND_URL}/proofs/${jobId}`);
+    const job = await res.json();
+    if (!res.ok) throw new Error(job.error ?? `HTTP ${res.status}`);
+    if (job.status === "fulfilled") return job.result.proof_token;
+    if (job.status === "expired" || job.status === "failed") {
+      throw new Error(job.error ?? `proof job ${job.status}`);
+    }
+    await sleep(5000);
+  }
+};
+
 export default function GenerateProof() {
-  const { isConnected } = useAccount();
+  const { address, isConnected } = useAccount();
   const [step, setStep] = React.useState("idle"); //'idle' | 'approving' | 'paying'
   const [approveTxHash, setApproveTxHash] = React.useState(null); // `0x${string}` | null
   const [payTxHash, setPayTxHash] = React.useState(null);
 
   const [zkProofKey , setZkProofKey] = React.useState(null);
-  
+  const [authExpiry, setAuthExpiry] = React.useState(null);
+
 
   const [monthSelected, setMonthSelected] = React.useState(6);
   const [yearSelected, setYearSelected] = React.useState(2025);
@@ -45,13 +73,61 @@ export default function GenerateProof() {
   const { writeContractAsync: writeApprove } = useWriteContract();
   const { writeContractAsync: writePay } = useWriteContract();
 
-  const { signMessageAsync} = useSignMessage()
+  const { signTypedDataAsync } = useSignTypedData()
+  const { signMessageAsync } = useSignMessage()
 
 
   const handleSign = async() => {
-    const signData = await signMessageAsync({ message: 'This message proofs your ownership of your address in zk proof' })
-    console.log("signature",signData);
-    setZkProofKey(zkKey);
+    // Authorize a proof of payment for this service and month, valid for one hour
+    const expiry = BigInt(Math.floor(Date.now() / 1000) + 3600);
+    const signData = await signTypedDataAsync({
+      domain: {
+        name: "fckuipaid",
+        version: "1",
+        chainId: 421614,
+        verifyingContract: SMARTCONTRACTADDRESS,
+      },
+      types: {
+        LoginAuthorization: [
+          { name: "paymentReceiver", type: "address" },
+          { name: "chainId", type: "uint64" },
+          { name: "month", type: "uint64" },
+          { name: "expiry", type: "uint64" },
+        ],
+      },
+      primaryType: "LoginAuthorization",
+      message: {
+        paymentReceiver: SMARTCONTRACTADDRESS,
+        chainId: 421614n,
+        month: billingPeriod(monthSelected, yearSelected),
+        expiry,
+      },
+    })
+    setAuthExpiry(Number(expiry));
+    // Deterministic signature the prover derives the private nullifier key from
+    const nullifierKey = await signMessageAsync({ message: NULLIFIER_KEY_MESSAGE });
+
+    setZkProofKey("Generating proof...");
+    try {
+      const res = await fetch(`${BACKEND_URL}/generate-proof`, {
+        method: "POST",
+        headers: { "Content-Type": "application/json" },
+        body: JSON.stringify({
+          user_address: address,
+          payment_receiver: SMARTCONTRACTADDRESS,
+          month: `${yearSelected}-${String(monthSelected).padStart(2, "0")}`,
+          signature: signData,
+          nullifier_key: nullifierKey,
+          expiry: Number(expiry),
+        }),
+      });
+      const job = await res.json();
+      if (!res.ok) throw new Error(job.error ?? `HTTP ${res.status}`);
+      setZkProofKey(await waitForProof(job.job_id));
+    } catch (error) {
+      console.error("Proof generation failed:", error);
+      setZkProofKey(`Proof generation failed: ${error.message}`);
+    }
   }
 
 
@@ -85,7 +161,7 @@ export default function GenerateProof() {
         abi: SMARTCONTRACTABI,
         address: SMARTCONTRACTADDRESS,
         functionName: "pay",
-        args: [BigInt(monthSelected.toString() + yearSelected.toString())], //monthandYear
+        args: [billingPeriod(monthSelected, yearSelected)],
         onSuccess: (data) => {
           console.log("calling pay success");
           setPayTxHash(data.hash);
@@ -293,6 +369,14 @@ export default function GenerateProof() {
               </Box>
             </Box>
           </Center>
+          {authExpiry && (
+            <Center>
+              <Text fontSize="sm" color="gray.600">
+                Login authorization valid until{" "}
+                {new Date(authExpiry * 1000).toLocaleString()}
+              </Text>
+            </Center>
+          )}
           <Center>
             <Box className="proof-text-holder">
               <Textarea
@@ -0,0 +1,64 @@
+use alloc::format;
+
+use alloy_primitives::{keccak256, uint, Signature, B256, U256};
+use alloy_sol_types::{eip712_domain, sol, SolStruct};
+
+/// Message signed with `personal_sign` in legacy signature mode
+pub const LEGACY_LOGIN_MESSAGE: &[u8] =
+    b"This message proofs your ownership of your address in zk proof";
+
+/// Half the order of secp256k1, the largest `s` of a normalized signature
+pub const SECP256K1N_HALF: U256 =
+    uint!(0x7FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF5D576E7357A4501DDFE92F46681B20A0_U256);
+
+sol! {
+    /// EIP-712 typed data the user signs to authorize a proof of payment.
+    struct LoginAuthorization {
+        address paymentReceiver;
+        uint64 chainId;
+        uint64 month;
+        uint64 expiry;
+    }
+}
+
+impl LoginAuthorization {
+    /// EIP-712 signing hash, under the `fckuipaid` domain of the payment receiver
+    pub fn signing_hash(&self) -> B256 {
+        let domain = eip712_domain! {
+            name: "fckuipaid",
+            version: "1",
+            chain_id: self.chainId,
+            verifying_contract: self.paymentReceiver,
+        };
+        self.eip712_signing_hash(&domain)
+    }
+}
+
+/// Message the guest recovers the signer from (via `personal_sign`) in legacy signature mode
+pub fn legacy_signing_hash() -> B256 {
+    // Ethereum signed message prefix
+    let prefix = format!(
+        "\x19Ethereum Signed Message:\n{}",
+        LEGACY_LOGIN_MESSAGE.len()
+    );
+    keccak256([prefix.as_bytes(), LEGACY_LOGIN_MESSAGE].concat())
+}
+
+/// Whether the signature is normalized to low `s`, which the guest requires. A
+/// high-`s` signature is the malleable twin of a low-`s` one for the same message.
+pub fn is_low_s(signature: &Signature) -> bool {
+    signature.s() <= SECP256K1N_HALF
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_low_s_signatures_are_normalized() {
+        let signature = |s: U256| Signature::new(U256::from(1), s, false);
+        assert!(is_low_s(&signature(U256::from(1))));
+        assert!(is_low_s(&signature(SECP256K1N_HALF)));
+        assert!(!is_low_s(&signature(SECP256K1N_HALF + U256::from(1))));
+    }
+}
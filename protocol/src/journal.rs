@@ -0,0 +1,69 @@
+use alloy_primitives::{uint, B256, U256};
+use alloy_sol_types::sol;
+
+sol! {
+    /// Steel commitment to the block the payment was checked against, ABI compatible
+    /// with `risc0_steel::Commitment`.
+    #[derive(Debug, PartialEq, Eq)]
+    struct Commitment {
+        uint256 id;
+        bytes32 digest;
+        bytes32 configID;
+    }
+
+    /// Data committed to by the payment check guest.
+    struct Journal {
+        Commitment commitment;
+        address paymentContract;
+        uint64 chainId;
+        uint64 month;
+        bytes32 challenge;
+        bytes32 nullifier;
+        uint64 epoch;
+        uint64 epochLength;
+        uint64 expiry;
+        bool legacySignature;
+        uint64 blockTimestamp;
+        uint64 gracePeriod;
+    }
+}
+
+impl Commitment {
+    /// Commitment to the block `id` of the given version, e.g. `0` for a block hash
+    pub const fn new(version: u16, id: u64, digest: B256, config_id: B256) -> Self {
+        Self {
+            id: Self::encode_id(id, version),
+            digest,
+            configID: config_id,
+        }
+    }
+
+    /// Pack the version into the top 16 bits of the ID
+    pub const fn encode_id(id: u64, version: u16) -> U256 {
+        U256::from_limbs([id, 0, 0, (version as u64) << 48])
+    }
+
+    /// The ID and the version of the commitment
+    pub fn decode_id(&self) -> (U256, u16) {
+        let id = self.id
+            & uint!(0x0000FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF_U256);
+        let version = (self.id.as_limbs()[3] >> 48) as u16;
+        (id, version)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commitment_ids_carry_the_version() {
+        let commitment = Commitment::new(1, 100, B256::ZERO, B256::ZERO);
+        assert_eq!(commitment.id >> 240, U256::from(1));
+        assert_eq!(commitment.decode_id(), (U256::from(100), 1));
+        assert_eq!(
+            Commitment::new(0, u64::MAX, B256::ZERO, B256::ZERO).decode_id(),
+            (U256::from(u64::MAX), 0)
+        );
+    }
+}
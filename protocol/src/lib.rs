@@ -0,0 +1,21 @@
+//! Data exchanged between the fckuipaid payment check guest and its verifiers.
+//!
+//! The guest commits a [`Journal`] after recovering the signer of a
+//! [`LoginAuthorization`] and deriving the user's nullifier with [`derive_nullifier`];
+//! the backend, the publisher and web2 services decode the journal and check
+//! signatures with the same definitions. The crate is `no_std`
+//! so it builds for the zkVM guest as well as for the host.
+
+#![no_std]
+
+extern crate alloc;
+
+mod authorization;
+mod journal;
+mod nullifier;
+
+pub use authorization::{
+    is_low_s, legacy_signing_hash, LoginAuthorization, LEGACY_LOGIN_MESSAGE, SECP256K1N_HALF,
+};
+pub use journal::{Commitment, Journal};
+pub use nullifier::{derive_nullifier, NULLIFIER_KEY_MESSAGE};
@@ -0,0 +1,62 @@
+use alloy_primitives::{keccak256, Address, Signature, B256};
+use alloy_sol_types::SolValue;
+
+/// Message the user signs with `personal_sign` to derive their nullifier key. Wallets
+/// sign deterministically (RFC 6979), so the signature is a stable secret of the user.
+pub const NULLIFIER_KEY_MESSAGE: &str =
+    "Derive my fckuipaid nullifier key (v1). Only sign this on fckuipaid.";
+
+/// Domain separator of the nullifier hash
+const NULLIFIER_DOMAIN: &[u8] = b"fckuipaid.nullifier.v2";
+
+/// Nullifier of a user for one payment receiver and epoch.
+///
+/// It is derived from the user's low-`s` signature of [`NULLIFIER_KEY_MESSAGE`], which
+/// never leaves the guest, so services cannot link the nullifiers of one user across
+/// services or epochs from public data such as the user's address. Only `r` and `s`
+/// are hashed, as the recovery ID does not change the signature.
+pub fn derive_nullifier(
+    nullifier_key: &Signature,
+    payment_contract: Address,
+    chain_id: u64,
+    epoch: u64,
+) -> B256 {
+    let secret = keccak256((nullifier_key.r(), nullifier_key.s()).abi_encode_packed());
+    keccak256(
+        [
+            NULLIFIER_DOMAIN,
+            &(secret, payment_contract, chain_id, epoch).abi_encode_packed(),
+        ]
+        .concat(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_primitives::{address, U256};
+
+    use super::*;
+
+    const RECEIVER: Address = address!("6fEDEb0B4942A8b438AFE68ba7c8Af4637c41903");
+
+    fn key(r: u64) -> Signature {
+        Signature::new(U256::from(r), U256::from(2), false)
+    }
+
+    #[test]
+    fn nullifiers_are_stable_per_key_receiver_and_epoch() {
+        let nullifier = derive_nullifier(&key(1), RECEIVER, 421614, 7);
+        assert_eq!(nullifier, derive_nullifier(&key(1), RECEIVER, 421614, 7));
+        // The recovery ID is not part of the secret
+        let flipped = Signature::new(U256::from(1), U256::from(2), true);
+        assert_eq!(nullifier, derive_nullifier(&flipped, RECEIVER, 421614, 7));
+
+        assert_ne!(nullifier, derive_nullifier(&key(3), RECEIVER, 421614, 7));
+        assert_ne!(
+            nullifier,
+            derive_nullifier(&key(1), Address::ZERO, 421614, 7)
+        );
+        assert_ne!(nullifier, derive_nullifier(&key(1), RECEIVER, 42161, 7));
+        assert_ne!(nullifier, derive_nullifier(&key(1), RECEIVER, 421614, 8));
+    }
+}
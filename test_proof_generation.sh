@@ -16,12 +16,24 @@ NC='\033[0m' # No Color
 SERVER_URL="http://localhost:3001"
 HEALTH_ENDPOINT="$SERVER_URL/health"
 PROOF_ENDPOINT="$SERVER_URL/generate-proof"
+JOBS_ENDPOINT="$SERVER_URL/proofs"
+POLL_INTERVAL=5
+POLL_TIMEOUT=900
+
+# Job created by the proof generation test, repeated requests must get it back
+FIRST_JOB_ID=""
 
 # Test data
 TEST_USER_ADDRESS="0x170f6F7b0925CF1447BAAF25a5AE61253EF31c1B"
 TEST_PAYMENT_RECEIVER="0x6fEDEb0B4942A8b438AFE68ba7c8Af4637c41903"
-TEST_MONTH=72025
+# Current billing period on chain (months since January 1970); only the current
+# period can be proven, so the test user must have paid for it
+TEST_MONTH=${TEST_MONTH:-$(( ($(date -u +%Y) - 1970) * 12 + 10#$(date -u +%m) - 1 ))}
+# Signature of the fixed legacy message, the server must run with ALLOW_LEGACY_SIGNATURES=true
 TEST_SIGNATURE="0x184bdcdfb9db09b6f55c7bcdd3907e2a8555d599b63289fe1fe014864fe605bf01a0f53bcbc539f2070480162f745c847e77e4bcd8821a39ba416945c1b1666c1b"
+# personal_sign signature of the nullifier key message by the test user, e.g. from
+#   cast wallet sign --private-key <test user key> "Derive my fckuipaid nullifier key (v1). Only sign this on fckuipaid."
+TEST_NULLIFIER_KEY=${TEST_NULLIFIER_KEY:-}
 
 # Function to print colored output
 print_status() {
@@ -87,6 +99,10 @@ check_environment() {
     if [ -z "$ARB_RPC_URL" ]; then
         missing_vars+=("ARB_RPC_URL")
     fi
+
+    if [ -z "$TEST_NULLIFIER_KEY" ]; then
+        missing_vars+=("TEST_NULLIFIER_KEY")
+    fi
     
     if [ ${#missing_vars[@]} -gt 0 ]; then
         print_error "Missing or invalid environment variables:"
@@ -109,7 +125,9 @@ test_proof_generation() {
         "user_address": "'$TEST_USER_ADDRESS'",
         "payment_receiver": "'$TEST_PAYMENT_RECEIVER'",
         "month": '$TEST_MONTH',
-        "signature": "'$TEST_SIGNATURE'"
+        "signature": "'$TEST_SIGNATURE'",
+        "nullifier_key": "'$TEST_NULLIFIER_KEY'",
+        "legacy_signature": true
     }'
     
     print_status "Sending request to $PROOF_ENDPOINT"
@@ -125,39 +143,58 @@ test_proof_generation() {
     print_status "HTTP Status Code: $http_code"
     print_status "Response: $response_body"
     
-    if [ "$http_code" -eq 200 ]; then
-        # Parse JSON response to check success field
-        local success=$(echo "$response_body" | grep -o '"success":[^,]*' | cut -d':' -f2 | tr -d ' ')
-        
-        if [ "$success" = "true" ]; then
-            print_success "Proof generation successful!"
-            
-            # Extract key fields from response
-            local proof_id=$(echo "$response_body" | grep -o '"proof_id":"[^"]*"' | cut -d'"' -f4)
-            local message=$(echo "$response_body" | grep -o '"message":"[^"]*"' | cut -d'"' -f4)
-            
-            print_success "Proof ID: $proof_id"
-            print_success "Message: $message"
-            
-            # Check if we got journal, seal, and commitment
-            if echo "$response_body" | grep -q '"journal":"[^"]*"' && \
-               echo "$response_body" | grep -q '"seal":"[^"]*"' && \
-               echo "$response_body" | grep -q '"commitment":"[^"]*"'; then
-                print_success "Response contains journal, seal, and commitment data"
-            else
-                print_warning "Response missing some proof data fields"
-            fi
-            
-            return 0
-        else
-            print_error "Proof generation failed (success=false)"
-            return 1
-        fi
-    else
+    if [ "$http_code" -ne 202 ]; then
         print_error "HTTP request failed with status code: $http_code"
         print_error "Response: $response_body"
         return 1
     fi
+
+    local job_id=$(echo "$response_body" | grep -o '"job_id":"[^"]*"' | cut -d'"' -f4)
+    FIRST_JOB_ID="$job_id"
+    print_success "Proof job created: $job_id"
+
+    # Poll the job until it reaches a final state
+    local waited=0
+    local job_body=""
+    local status=""
+    while [ "$waited" -lt "$POLL_TIMEOUT" ]; do
+        job_body=$(curl -s "$JOBS_ENDPOINT/$job_id")
+        status=$(echo "$job_body" | grep -o '"status":"[^"]*"' | cut -d'"' -f4)
+        print_status "Job status: $status (${waited}s)"
+
+        if [ "$status" = "fulfilled" ] || [ "$status" = "expired" ] || [ "$status" = "failed" ]; then
+            break
+        fi
+
+        sleep "$POLL_INTERVAL"
+        waited=$((waited + POLL_INTERVAL))
+    done
+
+    if [ "$status" != "fulfilled" ]; then
+        print_error "Proof job did not succeed (status=$status)"
+        print_error "Response: $job_body"
+        return 1
+    fi
+
+    print_success "Proof generation successful!"
+
+    # Extract key fields from the job result
+    local proof_id=$(echo "$job_body" | grep -o '"proof_id":"[^"]*"' | cut -d'"' -f4)
+    local message=$(echo "$job_body" | grep -o '"message":"[^"]*"' | cut -d'"' -f4)
+
+    print_success "Proof ID: $proof_id"
+    print_success "Message: $message"
+
+    # Check if we got journal, seal, and commitment
+    if echo "$job_body" | grep -q '"journal":"[^"]*"' && \
+       echo "$job_body" | grep -q '"seal":"[^"]*"' && \
+       echo "$job_body" | grep -q '"commitment":"[^"]*"'; then
+        print_success "Response contains journal, seal, and commitment data"
+    else
+        print_warning "Response missing some proof data fields"
+    fi
+
+    return 0
 }
 
 # Function to test invalid requests
@@ -169,7 +206,9 @@ test_invalid_requests() {
     local invalid_request1='{
         "payment_receiver": "'$TEST_PAYMENT_RECEIVER'",
         "month": '$TEST_MONTH',
-        "signature": "'$TEST_SIGNATURE'"
+        "signature": "'$TEST_SIGNATURE'",
+        "nullifier_key": "'$TEST_NULLIFIER_KEY'",
+        "legacy_signature": true
     }'
     
     local response1=$(curl -s -w "\n%{http_code}" -X POST "$PROOF_ENDPOINT" \
@@ -190,7 +229,9 @@ test_invalid_requests() {
         "user_address": "invalid_address",
         "payment_receiver": "'$TEST_PAYMENT_RECEIVER'",
         "month": '$TEST_MONTH',
-        "signature": "'$TEST_SIGNATURE'"
+        "signature": "'$TEST_SIGNATURE'",
+        "nullifier_key": "'$TEST_NULLIFIER_KEY'",
+        "legacy_signature": true
     }'
     
     local response2=$(curl -s -w "\n%{http_code}" -X POST "$PROOF_ENDPOINT" \
@@ -214,7 +255,9 @@ test_performance() {
         "user_address": "'$TEST_USER_ADDRESS'",
         "payment_receiver": "'$TEST_PAYMENT_RECEIVER'",
         "month": '$TEST_MONTH',
-        "signature": "'$TEST_SIGNATURE'"
+        "signature": "'$TEST_SIGNATURE'",
+        "nullifier_key": "'$TEST_NULLIFIER_KEY'",
+        "legacy_signature": true
     }'
     
     print_status "Measuring response time..."
@@ -228,22 +271,28 @@ test_performance() {
     local duration=$(echo "$end_time - $start_time" | bc -l)
     
     local http_code=$(echo "$response" | tail -n1)
+    local job_id=$(echo "$response" | sed '$d' | grep -o '"job_id":"[^"]*"' | cut -d'"' -f4)
     
-    if [ "$http_code" -eq 200 ]; then
-        print_success "Performance test completed in ${duration}s"
+    # The request repeats the one of the proof generation test, so it gets that
+    # job back (200); only if that job failed is a new one submitted (202)
+    if [ "$http_code" -eq 200 ] && [ -n "$FIRST_JOB_ID" ] && [ "$job_id" != "$FIRST_JOB_ID" ]; then
+        print_error "Repeated request returned job $job_id instead of $FIRST_JOB_ID"
+        return 1
+    fi
+    if [ "$http_code" -eq 200 ] || [ "$http_code" -eq 202 ]; then
+        print_success "Proof job $job_id returned in ${duration}s (HTTP $http_code)"
         
-        # Categorize performance
+        # Submission should not wait for the prover
         if (( $(echo "$duration < 10" | bc -l) )); then
             print_success "Excellent performance (< 10s)"
         elif (( $(echo "$duration < 30" | bc -l) )); then
             print_success "Good performance (< 30s)"
-        elif (( $(echo "$duration < 60" | bc -l) )); then
-            print_warning "Acceptable performance (< 60s)"
         else
-            print_warning "Slow performance (> 60s) - this is expected for ZK proof generation"
+            print_warning "Slow submission (> 30s)"
         fi
     else
         print_error "Performance test failed with HTTP $http_code"
+        return 1
     fi
 }
 
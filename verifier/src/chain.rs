@@ -0,0 +1,341 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use alloy_primitives::B256;
+use async_trait::async_trait;
+use fckuipaid_protocol::Commitment;
+
+use crate::time::unix_timestamp;
+
+/// Steel commitment version for block hash commitments
+const BLOCK_COMMITMENT_VERSION: u16 = 0;
+
+/// Canonical chain data of a block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockHeaderInfo {
+    pub number: u64,
+    pub hash: B256,
+    pub timestamp: u64,
+}
+
+/// Errors when validating a Steel commitment against the chain
+#[derive(Debug, thiserror::Error)]
+pub enum CommitmentError {
+    #[error("unsupported commitment version {0}")]
+    UnsupportedVersion(u16),
+    #[error("commitment block number {0} is out of range")]
+    InvalidBlockNumber(String),
+    #[error("commitment config ID {found} does not match expected {expected}")]
+    ConfigMismatch { expected: B256, found: B256 },
+    #[error("block {0} is not known to the chain")]
+    BlockNotFound(u64),
+    #[error("block {number} has hash {canonical}, commitment has {committed}")]
+    BlockHashMismatch {
+        number: u64,
+        canonical: B256,
+        committed: B256,
+    },
+    #[error("block {number} is {age:?} old, maximum is {max_age:?}")]
+    BlockTooOld {
+        number: u64,
+        age: Duration,
+        max_age: Duration,
+    },
+    #[error("failed to fetch block header: {0}")]
+    HeaderSource(String),
+}
+
+impl CommitmentError {
+    /// Machine-readable reason code
+    pub fn reason(&self) -> &'static str {
+        match self {
+            Self::UnsupportedVersion(_) | Self::InvalidBlockNumber(_) => "UNSUPPORTED_COMMITMENT",
+            Self::ConfigMismatch { .. } => "CHAIN_CONFIG_MISMATCH",
+            Self::BlockNotFound(_) => "BLOCK_NOT_FOUND",
+            Self::BlockHashMismatch { .. } => "BLOCK_HASH_MISMATCH",
+            Self::BlockTooOld { .. } => "BLOCK_TOO_OLD",
+            Self::HeaderSource(_) => "CHAIN_UNAVAILABLE",
+        }
+    }
+}
+
+/// Source of canonical block headers
+#[async_trait]
+pub trait HeaderSource: Send + Sync {
+    /// Header of the canonical block with the given number, if it exists
+    async fn header_by_number(
+        &self,
+        number: u64,
+    ) -> Result<Option<BlockHeaderInfo>, CommitmentError>;
+}
+
+/// Fetches headers from a JSON-RPC endpoint
+#[cfg(feature = "rpc")]
+pub struct RpcHeaderSource {
+    provider: alloy::providers::DynProvider,
+}
+
+#[cfg(feature = "rpc")]
+impl RpcHeaderSource {
+    pub fn new(rpc_url: url::Url) -> Self {
+        use alloy::providers::{Provider, ProviderBuilder};
+
+        Self {
+            provider: ProviderBuilder::new().connect_http(rpc_url).erased(),
+        }
+    }
+}
+
+#[cfg(feature = "rpc")]
+#[async_trait]
+impl HeaderSource for RpcHeaderSource {
+    async fn header_by_number(
+        &self,
+        number: u64,
+    ) -> Result<Option<BlockHeaderInfo>, CommitmentError> {
+        use alloy::{eips::BlockNumberOrTag, providers::Provider};
+
+        let block = self
+            .provider
+            .get_block_by_number(BlockNumberOrTag::Number(number))
+            .await
+            .map_err(|e| CommitmentError::HeaderSource(e.to_string()))?;
+
+        Ok(block.map(|block| BlockHeaderInfo {
+            number: block.header.number,
+            hash: block.header.hash,
+            timestamp: block.header.timestamp,
+        }))
+    }
+}
+
+/// Caches headers of another source by block number
+pub struct CachedHeaderSource<S> {
+    inner: S,
+    cache: Mutex<HashMap<u64, BlockHeaderInfo>>,
+    capacity: usize,
+}
+
+impl<S> CachedHeaderSource<S> {
+    pub fn new(inner: S, capacity: usize) -> Self {
+        Self {
+            inner,
+            cache: Mutex::new(HashMap::new()),
+            capacity,
+        }
+    }
+}
+
+#[async_trait]
+impl<S: HeaderSource> HeaderSource for CachedHeaderSource<S> {
+    async fn header_by_number(
+        &self,
+        number: u64,
+    ) -> Result<Option<BlockHeaderInfo>, CommitmentError> {
+        if let Some(header) = self.cache.lock().unwrap().get(&number) {
+            return Ok(Some(*header));
+        }
+
+        let header = self.inner.header_by_number(number).await?;
+        if let Some(header) = header {
+            let mut cache = self.cache.lock().unwrap();
+            if cache.len() >= self.capacity {
+                // Evict the oldest block, it is the least likely to be requested again
+                if let Some(oldest) = cache.keys().min().copied() {
+                    cache.remove(&oldest);
+                }
+            }
+            cache.insert(number, header);
+        }
+        Ok(header)
+    }
+}
+
+/// Checks that a Steel commitment refers to a recent block of the canonical chain
+#[derive(Clone)]
+pub struct CommitmentValidator {
+    source: Arc<dyn HeaderSource>,
+    max_block_age: Duration,
+    expected_config_id: Option<B256>,
+}
+
+impl CommitmentValidator {
+    pub fn new(source: Arc<dyn HeaderSource>, max_block_age: Duration) -> Self {
+        Self {
+            source,
+            max_block_age,
+            expected_config_id: None,
+        }
+    }
+
+    /// Also require the commitment to use the given chain configuration
+    pub fn with_config_id(mut self, config_id: B256) -> Self {
+        self.expected_config_id = Some(config_id);
+        self
+    }
+
+    /// Validate the commitment, returning the canonical header it refers to
+    pub async fn validate(
+        &self,
+        commitment: &Commitment,
+    ) -> Result<BlockHeaderInfo, CommitmentError> {
+        let (id, version) = commitment.decode_id();
+        if version != BLOCK_COMMITMENT_VERSION {
+            return Err(CommitmentError::UnsupportedVersion(version));
+        }
+        let number: u64 = id
+            .try_into()
+            .map_err(|_| CommitmentError::InvalidBlockNumber(id.to_string()))?;
+
+        if let Some(expected) = self.expected_config_id {
+            if commitment.configID != expected {
+                return Err(CommitmentError::ConfigMismatch {
+                    expected,
+                    found: commitment.configID,
+                });
+            }
+        }
+
+        let header = self
+            .source
+            .header_by_number(number)
+            .await?
+            .ok_or(CommitmentError::BlockNotFound(number))?;
+        if header.hash != commitment.digest {
+            return Err(CommitmentError::BlockHashMismatch {
+                number,
+                canonical: header.hash,
+                committed: commitment.digest,
+            });
+        }
+
+        let age = Duration::from_secs(unix_timestamp().saturating_sub(header.timestamp));
+        if age > self.max_block_age {
+            return Err(CommitmentError::BlockTooOld {
+                number,
+                age,
+                max_age: self.max_block_age,
+            });
+        }
+
+        Ok(header)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    /// Chain of known headers, counting lookups
+    #[derive(Default)]
+    struct MockHeaderSource {
+        headers: HashMap<u64, BlockHeaderInfo>,
+        lookups: AtomicUsize,
+    }
+
+    impl MockHeaderSource {
+        fn with_block(mut self, number: u64, age: Duration) -> Self {
+            let header = BlockHeaderInfo {
+                number,
+                hash: block_hash(number),
+                timestamp: unix_timestamp() - age.as_secs(),
+            };
+            self.headers.insert(number, header);
+            self
+        }
+    }
+
+    #[async_trait]
+    impl HeaderSource for MockHeaderSource {
+        async fn header_by_number(
+            &self,
+            number: u64,
+        ) -> Result<Option<BlockHeaderInfo>, CommitmentError> {
+            self.lookups.fetch_add(1, Ordering::Relaxed);
+            Ok(self.headers.get(&number).copied())
+        }
+    }
+
+    fn block_hash(number: u64) -> B256 {
+        B256::left_padding_from(&number.to_be_bytes())
+    }
+
+    fn validator() -> CommitmentValidator {
+        let source = MockHeaderSource::default()
+            .with_block(100, Duration::from_secs(60))
+            .with_block(50, Duration::from_secs(7200));
+        CommitmentValidator::new(Arc::new(source), Duration::from_secs(3600))
+    }
+
+    fn commitment(number: u64, digest: B256) -> Commitment {
+        Commitment::new(BLOCK_COMMITMENT_VERSION, number, digest, B256::ZERO)
+    }
+
+    async fn reason(validator: &CommitmentValidator, commitment: Commitment) -> &'static str {
+        validator.validate(&commitment).await.unwrap_err().reason()
+    }
+
+    #[tokio::test]
+    async fn accepts_recent_canonical_blocks() {
+        let header = validator()
+            .validate(&commitment(100, block_hash(100)))
+            .await
+            .unwrap();
+        assert_eq!(header.number, 100);
+        assert_eq!(header.hash, block_hash(100));
+    }
+
+    #[tokio::test]
+    async fn rejects_commitments_not_on_the_chain() {
+        let validator = validator();
+        assert_eq!(
+            reason(&validator, commitment(100, B256::repeat_byte(1))).await,
+            "BLOCK_HASH_MISMATCH"
+        );
+        assert_eq!(
+            reason(&validator, commitment(101, block_hash(101))).await,
+            "BLOCK_NOT_FOUND"
+        );
+        assert_eq!(
+            reason(&validator, commitment(50, block_hash(50))).await,
+            "BLOCK_TOO_OLD"
+        );
+    }
+
+    #[tokio::test]
+    async fn checks_version_and_config_id() {
+        let beacon = Commitment::new(1, 100, block_hash(100), B256::ZERO);
+        assert_eq!(reason(&validator(), beacon).await, "UNSUPPORTED_COMMITMENT");
+
+        let validator = validator().with_config_id(B256::repeat_byte(9));
+        assert_eq!(
+            reason(&validator, commitment(100, block_hash(100))).await,
+            "CHAIN_CONFIG_MISMATCH"
+        );
+        let matching = Commitment::new(0, 100, block_hash(100), B256::repeat_byte(9));
+        validator.validate(&matching).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn caches_headers_up_to_capacity() {
+        let source = MockHeaderSource::default()
+            .with_block(1, Duration::ZERO)
+            .with_block(2, Duration::ZERO)
+            .with_block(3, Duration::ZERO);
+        let cached = CachedHeaderSource::new(source, 2);
+
+        for number in [1, 2, 1, 2] {
+            assert!(cached.header_by_number(number).await.unwrap().is_some());
+        }
+        assert_eq!(cached.inner.lookups.load(Ordering::Relaxed), 2);
+
+        // Unknown blocks are not cached, and the oldest block makes room for a new one
+        assert!(cached.header_by_number(4).await.unwrap().is_none());
+        assert!(cached.header_by_number(3).await.unwrap().is_some());
+        assert!(cached.header_by_number(1).await.unwrap().is_some());
+        assert_eq!(cached.inner.lookups.load(Ordering::Relaxed), 5);
+    }
+}
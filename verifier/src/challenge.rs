@@ -0,0 +1,162 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use alloy_primitives::B256;
+
+use crate::time::unix_timestamp;
+
+/// Errors when redeeming a login challenge
+#[derive(Debug, thiserror::Error)]
+pub enum ChallengeError {
+    #[error("challenge {0} was not issued or was already used")]
+    Unknown(B256),
+    #[error("challenge {0} expired")]
+    Expired(B256),
+}
+
+impl ChallengeError {
+    /// Machine-readable reason code
+    pub fn reason(&self) -> &'static str {
+        match self {
+            Self::Unknown(_) => "UNKNOWN_CHALLENGE",
+            Self::Expired(_) => "CHALLENGE_EXPIRED",
+        }
+    }
+}
+
+/// Outstanding challenges, indexed by expiry so the oldest can be dropped first
+#[derive(Default)]
+struct Issued {
+    /// Expiry and issue sequence number of each challenge
+    challenges: HashMap<B256, (u64, u64)>,
+    /// Challenges by expiry and issue sequence number
+    by_expiry: BTreeMap<(u64, u64), B256>,
+    next_sequence: u64,
+}
+
+impl Issued {
+    fn insert(&mut self, challenge: B256, expires_at: u64) {
+        let key = (expires_at, self.next_sequence);
+        self.next_sequence += 1;
+        self.challenges.insert(challenge, key);
+        self.by_expiry.insert(key, challenge);
+    }
+
+    /// Remove a challenge, returning its expiry
+    fn remove(&mut self, challenge: &B256) -> Option<u64> {
+        let key = self.challenges.remove(challenge)?;
+        self.by_expiry.remove(&key);
+        Some(key.0)
+    }
+
+    fn remove_oldest(&mut self) {
+        if let Some((_, challenge)) = self.by_expiry.pop_first() {
+            self.challenges.remove(&challenge);
+        }
+    }
+}
+
+/// Issues single-use random challenges that users bind into their proofs
+///
+/// A proof is only accepted for the challenge the service handed out for the
+/// login session, so copied proofs cannot be replayed. Challenges are handed out
+/// without authentication, so at most `capacity` are kept outstanding and the
+/// oldest one is dropped to make room for a new one.
+pub struct ChallengeRegistry {
+    ttl: Duration,
+    capacity: usize,
+    issued: Mutex<Issued>,
+}
+
+impl ChallengeRegistry {
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        Self {
+            ttl,
+            capacity: capacity.max(1),
+            issued: Mutex::new(Issued::default()),
+        }
+    }
+
+    /// Issue a new challenge, returning it with its expiry timestamp
+    pub fn issue(&self) -> (B256, u64) {
+        let now = unix_timestamp();
+        let challenge = B256::random();
+        let expires_at = now + self.ttl.as_secs();
+
+        let mut issued = self.issued.lock().unwrap();
+        // Forget expired challenges, then drop the oldest ones until there is room
+        while issued
+            .by_expiry
+            .first_key_value()
+            .is_some_and(|(&(expiry, _), _)| {
+                expiry <= now || issued.challenges.len() >= self.capacity
+            })
+        {
+            issued.remove_oldest();
+        }
+        issued.insert(challenge, expires_at);
+        (challenge, expires_at)
+    }
+
+    /// Redeem a challenge. Each challenge can be redeemed once.
+    pub fn consume(&self, challenge: &B256) -> Result<(), ChallengeError> {
+        let expires_at = self
+            .issued
+            .lock()
+            .unwrap()
+            .remove(challenge)
+            .ok_or(ChallengeError::Unknown(*challenge))?;
+        if expires_at <= unix_timestamp() {
+            return Err(ChallengeError::Expired(*challenge));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn challenges_can_be_redeemed_once() {
+        let registry = ChallengeRegistry::new(Duration::from_secs(60), 10);
+        let (challenge, expires_at) = registry.issue();
+        assert!(expires_at > unix_timestamp());
+
+        registry.consume(&challenge).unwrap();
+        assert!(matches!(
+            registry.consume(&challenge),
+            Err(ChallengeError::Unknown(_))
+        ));
+        assert!(matches!(
+            registry.consume(&B256::random()),
+            Err(ChallengeError::Unknown(_))
+        ));
+    }
+
+    #[test]
+    fn expired_challenges_are_rejected() {
+        let registry = ChallengeRegistry::new(Duration::ZERO, 10);
+        let (challenge, _) = registry.issue();
+        let err = registry.consume(&challenge).unwrap_err();
+        assert_eq!(err.reason(), "CHALLENGE_EXPIRED");
+    }
+
+    #[test]
+    fn oldest_challenges_are_dropped_at_capacity() {
+        let registry = ChallengeRegistry::new(Duration::from_secs(60), 2);
+        let (first, _) = registry.issue();
+        let (second, _) = registry.issue();
+        let (third, _) = registry.issue();
+        assert_eq!(registry.issued.lock().unwrap().challenges.len(), 2);
+
+        assert!(matches!(
+            registry.consume(&first),
+            Err(ChallengeError::Unknown(_))
+        ));
+        registry.consume(&second).unwrap();
+        registry.consume(&third).unwrap();
+        assert!(registry.issued.lock().unwrap().by_expiry.is_empty());
+    }
+}
@@ -0,0 +1,31 @@
+//! Verification of fckuipaid proofs of payment.
+//!
+//! Web2 services gate logins by calling [`verify_proof_token`] with the token a
+//! user pasted and the image IDs of the payment check guest they accept, and
+//! then check the Steel commitment against the chain with a [`CommitmentValidator`].
+
+mod chain;
+mod challenge;
+mod nullifier;
+mod time;
+mod token;
+mod verifier;
+
+#[cfg(feature = "rpc")]
+pub use chain::RpcHeaderSource;
+pub use chain::{
+    BlockHeaderInfo, CachedHeaderSource, CommitmentError, CommitmentValidator, HeaderSource,
+};
+pub use challenge::{ChallengeError, ChallengeRegistry};
+pub use fckuipaid_billing::{BillingPeriod, BillingPeriodError};
+pub use fckuipaid_protocol::{
+    derive_nullifier, is_low_s, legacy_signing_hash, Commitment, Journal, LoginAuthorization,
+    LEGACY_LOGIN_MESSAGE, NULLIFIER_KEY_MESSAGE,
+};
+pub use nullifier::{NullifierError, NullifierPolicy, NullifierTracker};
+pub use risc0_zkvm::sha::Digest;
+pub use time::unix_timestamp;
+pub use token::{ProofToken, ProofTokenError, PROOF_TOKEN_PREFIX};
+pub use verifier::{
+    verify_proof_token, VerificationError, VerifiedPayment, Verifier, DEFAULT_MAX_GRACE_PERIOD,
+};
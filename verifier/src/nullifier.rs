@@ -0,0 +1,199 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use alloy_primitives::B256;
+
+use crate::time::unix_timestamp;
+
+/// Errors when recording a nullifier
+#[derive(Debug, thiserror::Error)]
+pub enum NullifierError {
+    #[error("proof is for epoch {epoch}, current epoch is {current}")]
+    EpochMismatch { epoch: u64, current: u64 },
+    #[error("proof is for epoch {epoch}, but its block is in epoch {block_epoch}")]
+    BlockOutsideEpoch { epoch: u64, block_epoch: u64 },
+    #[error("nullifier {nullifier} was used {limit} times in epoch {epoch}")]
+    LimitExceeded {
+        nullifier: B256,
+        epoch: u64,
+        limit: u32,
+    },
+}
+
+impl NullifierError {
+    /// Machine-readable reason code
+    pub fn reason(&self) -> &'static str {
+        match self {
+            Self::EpochMismatch { .. } => "EPOCH_MISMATCH",
+            Self::BlockOutsideEpoch { .. } => "EPOCH_BLOCK_MISMATCH",
+            Self::LimitExceeded { .. } => "NULLIFIER_LIMIT_EXCEEDED",
+        }
+    }
+}
+
+/// How nullifier epochs are derived and how often a nullifier may be used in one
+#[derive(Debug, Clone, Copy)]
+pub struct NullifierPolicy {
+    /// Length of an epoch; epoch `n` covers `[n * length, (n + 1) * length)` in unix time
+    pub epoch_length: Duration,
+    /// Maximum number of logins per nullifier and epoch
+    pub max_uses_per_epoch: u32,
+}
+
+impl NullifierPolicy {
+    /// Epoch containing the given unix timestamp
+    pub fn epoch_at(&self, timestamp: u64) -> u64 {
+        timestamp / self.epoch_length.as_secs().max(1)
+    }
+
+    /// Epoch containing the current time
+    pub fn current_epoch(&self) -> u64 {
+        self.epoch_at(unix_timestamp())
+    }
+}
+
+/// Counts nullifier usage per epoch to detect shared accounts
+///
+/// The guest derives one nullifier per user, payment receiver and epoch from the
+/// user's secret nullifier key, so a service can limit logins of one paid account
+/// without linking sessions across epochs or services. Proofs for the previous epoch are still accepted
+/// so that a proof generated right before an epoch boundary remains usable, but
+/// only if the block they were checked against lies in that epoch: otherwise a
+/// user could log in `max_uses_per_epoch` times with each of two epochs.
+pub struct NullifierTracker {
+    policy: NullifierPolicy,
+    uses: Mutex<HashMap<(u64, B256), u32>>,
+}
+
+impl NullifierTracker {
+    pub fn new(policy: NullifierPolicy) -> Self {
+        Self {
+            policy,
+            uses: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn policy(&self) -> &NullifierPolicy {
+        &self.policy
+    }
+
+    /// Record a login with the given nullifier, returning how often it was used in the epoch.
+    /// `block_timestamp` is the committed timestamp of the block the proof was checked against.
+    pub fn record(
+        &self,
+        nullifier: B256,
+        epoch: u64,
+        block_timestamp: u64,
+    ) -> Result<u32, NullifierError> {
+        let block_epoch = self.policy.epoch_at(block_timestamp);
+        if epoch != block_epoch {
+            return Err(NullifierError::BlockOutsideEpoch { epoch, block_epoch });
+        }
+        let current = self.policy.current_epoch();
+        if epoch != current && epoch + 1 != current {
+            return Err(NullifierError::EpochMismatch { epoch, current });
+        }
+
+        let mut uses = self.uses.lock().unwrap();
+        // Forget epochs that can no longer be presented
+        uses.retain(|(e, _), _| *e + 1 >= current);
+
+        let count = uses.entry((epoch, nullifier)).or_insert(0);
+        if *count >= self.policy.max_uses_per_epoch {
+            return Err(NullifierError::LimitExceeded {
+                nullifier,
+                epoch,
+                limit: self.policy.max_uses_per_epoch,
+            });
+        }
+        *count += 1;
+        Ok(*count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPOCH_LENGTH: u64 = 86400;
+
+    fn tracker(max_uses_per_epoch: u32) -> NullifierTracker {
+        NullifierTracker::new(NullifierPolicy {
+            epoch_length: Duration::from_secs(EPOCH_LENGTH),
+            max_uses_per_epoch,
+        })
+    }
+
+    #[test]
+    fn derives_epochs_from_timestamps() {
+        let policy = tracker(1).policy;
+        assert_eq!(policy.epoch_at(0), 0);
+        assert_eq!(policy.epoch_at(86399), 0);
+        assert_eq!(policy.epoch_at(86400), 1);
+        assert_eq!(policy.current_epoch(), unix_timestamp() / 86400);
+
+        let zero_length = NullifierPolicy {
+            epoch_length: Duration::ZERO,
+            max_uses_per_epoch: 1,
+        };
+        assert_eq!(zero_length.epoch_at(42), 42);
+    }
+
+    /// Record a login with a proof checked against a block of the given epoch
+    fn record(
+        tracker: &NullifierTracker,
+        nullifier: B256,
+        epoch: u64,
+    ) -> Result<u32, NullifierError> {
+        tracker.record(nullifier, epoch, epoch * EPOCH_LENGTH + 60)
+    }
+
+    #[test]
+    fn limits_uses_per_epoch() {
+        let tracker = tracker(2);
+        let epoch = tracker.policy().current_epoch();
+        let nullifier = B256::repeat_byte(1);
+
+        assert_eq!(record(&tracker, nullifier, epoch).unwrap(), 1);
+        assert_eq!(record(&tracker, nullifier, epoch).unwrap(), 2);
+        let err = record(&tracker, nullifier, epoch).unwrap_err();
+        assert_eq!(err.reason(), "NULLIFIER_LIMIT_EXCEEDED");
+
+        // Other nullifiers and the previous epoch are counted separately
+        assert_eq!(record(&tracker, B256::repeat_byte(2), epoch).unwrap(), 1);
+        assert_eq!(record(&tracker, nullifier, epoch - 1).unwrap(), 1);
+    }
+
+    #[test]
+    fn only_accepts_current_and_previous_epoch() {
+        let tracker = tracker(1);
+        let epoch = tracker.policy().current_epoch();
+        let nullifier = B256::repeat_byte(1);
+
+        for stale in [epoch - 2, epoch + 1] {
+            let err = record(&tracker, nullifier, stale).unwrap_err();
+            assert_eq!(err.reason(), "EPOCH_MISMATCH");
+        }
+        record(&tracker, nullifier, epoch - 1).unwrap();
+        record(&tracker, nullifier, epoch).unwrap();
+    }
+
+    #[test]
+    fn only_accepts_the_epoch_of_the_block() {
+        let tracker = tracker(1);
+        let epoch = tracker.policy().current_epoch();
+        let nullifier = B256::repeat_byte(1);
+        let block_in = |epoch: u64| epoch * EPOCH_LENGTH + 60;
+
+        // A proof checked against a block of the current epoch cannot claim the
+        // previous one, and vice versa
+        for (claimed, block) in [(epoch - 1, epoch), (epoch, epoch - 1)] {
+            let err = tracker
+                .record(nullifier, claimed, block_in(block))
+                .unwrap_err();
+            assert_eq!(err.reason(), "EPOCH_BLOCK_MISMATCH");
+        }
+        tracker.record(nullifier, epoch, block_in(epoch)).unwrap();
+    }
+}
@@ -0,0 +1,160 @@
+use alloy_primitives::B256;
+use alloy_sol_types::{sol, SolValue};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use risc0_zkvm::sha::Digest;
+use sha2::{Digest as _, Sha256};
+
+/// Prefix of version 1 proof tokens
+pub const PROOF_TOKEN_PREFIX: &str = "fkp1_";
+
+/// Number of checksum bytes appended to the token payload
+const CHECKSUM_LEN: usize = 4;
+
+sol! {
+    /// ABI layout of the proof token payload.
+    struct ProofTokenPayload {
+        bytes32 imageId;
+        bytes seal;
+        bytes journal;
+    }
+}
+
+/// Errors when decoding a proof token
+#[derive(Debug, thiserror::Error)]
+pub enum ProofTokenError {
+    #[error("token must start with fkp1_")]
+    InvalidPrefix,
+    #[error("token is not valid base64url: {0}")]
+    InvalidEncoding(#[from] base64::DecodeError),
+    #[error("token is too short")]
+    TooShort,
+    #[error("token checksum mismatch")]
+    ChecksumMismatch,
+    #[error("token payload is not valid: {0}")]
+    InvalidPayload(#[from] alloy_sol_types::Error),
+}
+
+/// Single copy-pasteable proof: ABI-encoded seal, journal and image ID
+///
+/// Encoded as `fkp1_<base64url(abi(imageId, seal, journal) || checksum)>`,
+/// where the checksum is the first 4 bytes of the SHA-256 of the ABI payload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProofToken {
+    /// Image ID of the guest program that produced the proof
+    pub image_id: Digest,
+    /// ABI-encoded seal as produced by `encode_seal`
+    pub seal: Vec<u8>,
+    /// Journal (public outputs) of the proof
+    pub journal: Vec<u8>,
+}
+
+impl ProofToken {
+    pub fn new(image_id: impl Into<Digest>, seal: Vec<u8>, journal: Vec<u8>) -> Self {
+        Self {
+            image_id: image_id.into(),
+            seal,
+            journal,
+        }
+    }
+
+    /// Encode the token as a `fkp1_` string
+    pub fn encode(&self) -> String {
+        let mut payload = ProofTokenPayload {
+            imageId: B256::from_slice(self.image_id.as_bytes()),
+            seal: self.seal.clone().into(),
+            journal: self.journal.clone().into(),
+        }
+        .abi_encode();
+        let checksum = Sha256::digest(&payload);
+        payload.extend_from_slice(&checksum[..CHECKSUM_LEN]);
+
+        format!("{}{}", PROOF_TOKEN_PREFIX, URL_SAFE_NO_PAD.encode(payload))
+    }
+
+    /// Decode a `fkp1_` string, checking its checksum
+    pub fn decode(token: &str) -> Result<Self, ProofTokenError> {
+        let encoded = token
+            .trim()
+            .strip_prefix(PROOF_TOKEN_PREFIX)
+            .ok_or(ProofTokenError::InvalidPrefix)?;
+        let bytes = URL_SAFE_NO_PAD.decode(encoded)?;
+        if bytes.len() <= CHECKSUM_LEN {
+            return Err(ProofTokenError::TooShort);
+        }
+
+        let (payload, checksum) = bytes.split_at(bytes.len() - CHECKSUM_LEN);
+        if Sha256::digest(payload)[..CHECKSUM_LEN] != *checksum {
+            return Err(ProofTokenError::ChecksumMismatch);
+        }
+
+        let payload = ProofTokenPayload::abi_decode(payload)?;
+        Ok(Self {
+            image_id: Digest::from_bytes(payload.imageId.0),
+            seal: payload.seal.to_vec(),
+            journal: payload.journal.to_vec(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token() -> ProofToken {
+        ProofToken::new(Digest::new([7; 8]), vec![1, 2, 3, 4], vec![5, 6, 7])
+    }
+
+    /// Encode raw payload bytes with a valid checksum
+    fn encode_payload(mut payload: Vec<u8>) -> String {
+        let checksum = Sha256::digest(&payload);
+        payload.extend_from_slice(&checksum[..CHECKSUM_LEN]);
+        format!("{}{}", PROOF_TOKEN_PREFIX, URL_SAFE_NO_PAD.encode(payload))
+    }
+
+    #[test]
+    fn round_trips_through_encoding() {
+        let encoded = token().encode();
+        assert!(encoded.starts_with(PROOF_TOKEN_PREFIX));
+        assert_eq!(ProofToken::decode(&encoded).unwrap(), token());
+        // Pasted tokens often carry surrounding whitespace
+        assert_eq!(
+            ProofToken::decode(&format!("  {}\n", encoded)).unwrap(),
+            token()
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_tokens() {
+        let encoded = token().encode();
+        assert!(matches!(
+            ProofToken::decode(encoded.trim_start_matches(PROOF_TOKEN_PREFIX)),
+            Err(ProofTokenError::InvalidPrefix)
+        ));
+        assert!(matches!(
+            ProofToken::decode("fkp1_not*base64"),
+            Err(ProofTokenError::InvalidEncoding(_))
+        ));
+        assert!(matches!(
+            ProofToken::decode("fkp1_AAAA"),
+            Err(ProofTokenError::TooShort)
+        ));
+        assert!(matches!(
+            ProofToken::decode(&encode_payload(vec![1, 2, 3])),
+            Err(ProofTokenError::InvalidPayload(_))
+        ));
+    }
+
+    #[test]
+    fn detects_corrupted_tokens() {
+        let encoded = token().encode();
+        let mut bytes = URL_SAFE_NO_PAD
+            .decode(encoded.trim_start_matches(PROOF_TOKEN_PREFIX))
+            .unwrap();
+        bytes[40] ^= 1;
+        let corrupted = format!("{}{}", PROOF_TOKEN_PREFIX, URL_SAFE_NO_PAD.encode(bytes));
+        assert!(matches!(
+            ProofToken::decode(&corrupted),
+            Err(ProofTokenError::ChecksumMismatch)
+        ));
+    }
+}
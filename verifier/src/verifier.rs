@@ -0,0 +1,503 @@
+use std::time::Duration;
+
+use alloy_primitives::{Address, Bytes, B256, U256};
+use alloy_sol_types::SolValue;
+use fckuipaid_billing::BillingPeriod;
+use fckuipaid_protocol::{Commitment, Journal};
+use risc0_ethereum_contracts::receipt::{decode_seal, Receipt};
+use risc0_zkvm::sha::Digest;
+
+use crate::time::unix_timestamp;
+use crate::token::{ProofToken, ProofTokenError};
+
+/// Errors when verifying a proof of payment
+#[derive(Debug, thiserror::Error)]
+pub enum VerificationError {
+    #[error("invalid proof token: {0}")]
+    InvalidToken(#[from] ProofTokenError),
+    #[error("proof is for image ID {0}, which is not accepted")]
+    UnknownImageId(Digest),
+    #[error("invalid seal: {0}")]
+    InvalidSeal(String),
+    #[error("set inclusion receipts are not supported for offchain verification")]
+    UnsupportedSealType,
+    #[error("receipt verification failed: {0}")]
+    InvalidReceipt(String),
+    #[error("invalid journal: {0}")]
+    InvalidJournal(String),
+    #[error("proof is for receiver {payment_contract} on chain {chain_id}, which is not accepted")]
+    WrongPaymentReceiver {
+        payment_contract: Address,
+        chain_id: u64,
+    },
+    #[error("proof is bound to challenge {found}, expected {expected}")]
+    ChallengeMismatch { expected: B256, found: B256 },
+    #[error("proof was authorized with a legacy signature, which is not accepted")]
+    LegacySignature,
+    #[error("login authorization expired at {0}")]
+    AuthorizationExpired(u64),
+    #[error("proof was checked against a block {age:?} old, maximum is {max_age:?}")]
+    StaleProof { age: Duration, max_age: Duration },
+    #[error("proof allows a grace period of {found:?}, maximum is {max:?}")]
+    GracePeriodTooLong { found: Duration, max: Duration },
+}
+
+impl VerificationError {
+    /// Machine-readable reason code
+    pub fn reason(&self) -> &'static str {
+        match self {
+            Self::InvalidToken(_) => "INVALID_PROOF_TOKEN",
+            Self::UnknownImageId(_) => "UNKNOWN_IMAGE_ID",
+            Self::InvalidSeal(_) => "INVALID_SEAL",
+            Self::UnsupportedSealType => "UNSUPPORTED_SEAL_TYPE",
+            Self::InvalidReceipt(_) => "INVALID_RECEIPT",
+            Self::InvalidJournal(_) => "INVALID_JOURNAL",
+            Self::WrongPaymentReceiver { .. } => "WRONG_PAYMENT_RECEIVER",
+            Self::ChallengeMismatch { .. } => "CHALLENGE_MISMATCH",
+            Self::LegacySignature => "LEGACY_SIGNATURE",
+            Self::AuthorizationExpired(_) => "AUTHORIZATION_EXPIRED",
+            Self::StaleProof { .. } => "STALE_PROOF",
+            Self::GracePeriodTooLong { .. } => "GRACE_PERIOD_TOO_LONG",
+        }
+    }
+}
+
+/// Public outputs of a verified proof of payment
+#[derive(Debug, Clone)]
+pub struct VerifiedPayment {
+    /// Image ID of the guest program that produced the proof
+    pub image_id: Digest,
+    /// Steel commitment to the block the payment was checked against
+    pub commitment: Commitment,
+    /// PaymentReceiver contract the payment was made to
+    pub payment_contract: Address,
+    /// Chain the PaymentReceiver contract is deployed on
+    pub chain_id: u64,
+    /// Billing period the payment was made for
+    pub period: BillingPeriod,
+    /// Service-issued challenge the proof is bound to
+    pub challenge: B256,
+    /// Per-service, per-epoch pseudonym of the user
+    pub nullifier: B256,
+    /// Epoch the nullifier was derived for, the one containing `block_timestamp`
+    pub epoch: u64,
+    /// Length of the nullifier epochs the guest derived `epoch` with
+    pub epoch_length: Duration,
+    /// Unix timestamp after which the user's login authorization is no longer valid
+    pub expiry: u64,
+    /// Whether the user signed the fixed legacy message instead of an EIP-712 authorization
+    pub legacy_signature: bool,
+    /// Timestamp of the block the payment was checked against
+    pub block_timestamp: u64,
+    /// How long after the end of `period` the block may be
+    pub grace_period: Duration,
+}
+
+impl VerifiedPayment {
+    /// Block number (or beacon timestamp) and version of the Steel commitment
+    pub fn commitment_id(&self) -> (U256, u16) {
+        self.commitment.decode_id()
+    }
+
+    /// Block hash (or beacon root) of the Steel commitment
+    pub fn block_digest(&self) -> B256 {
+        self.commitment.digest
+    }
+
+    /// Check that the proof is bound to the challenge issued for this login
+    pub fn check_challenge(&self, expected: B256) -> Result<(), VerificationError> {
+        if self.challenge != expected {
+            return Err(VerificationError::ChallengeMismatch {
+                expected,
+                found: self.challenge,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Longest grace period a [`Verifier`] accepts unless configured otherwise
+pub const DEFAULT_MAX_GRACE_PERIOD: Duration = Duration::from_secs(3 * 86400);
+
+/// Verifies proofs of payment against a set of accepted guest image IDs
+/// and the PaymentReceiver contracts of the service
+#[derive(Debug, Clone)]
+pub struct Verifier {
+    accepted_image_ids: Vec<Digest>,
+    accepted_receivers: Vec<(u64, Address)>,
+    accept_any_receiver: bool,
+    allow_legacy_signatures: bool,
+    max_block_age: Option<Duration>,
+    max_grace_period: Duration,
+}
+
+impl Verifier {
+    pub fn new<I, D>(accepted_image_ids: I) -> Self
+    where
+        I: IntoIterator<Item = D>,
+        D: Into<Digest>,
+    {
+        Self {
+            accepted_image_ids: accepted_image_ids.into_iter().map(Into::into).collect(),
+            accepted_receivers: Vec::new(),
+            accept_any_receiver: false,
+            allow_legacy_signatures: false,
+            max_block_age: None,
+            max_grace_period: DEFAULT_MAX_GRACE_PERIOD,
+        }
+    }
+
+    /// Accept payments to the given PaymentReceiver contract. Can be called
+    /// repeatedly; without any receiver, no proof is accepted.
+    pub fn with_payment_receiver(mut self, chain_id: u64, payment_contract: Address) -> Self {
+        self.accepted_receivers.push((chain_id, payment_contract));
+        self
+    }
+
+    /// Accept payments to every PaymentReceiver contract, leaving the receiver
+    /// check to the caller
+    pub fn accept_any_payment_receiver(mut self) -> Self {
+        self.accept_any_receiver = true;
+        self
+    }
+
+    /// Also accept proofs signed with the fixed legacy message, which carry no expiry
+    pub fn allow_legacy_signatures(mut self, allow: bool) -> Self {
+        self.allow_legacy_signatures = allow;
+        self
+    }
+
+    /// Only accept proofs checked against a block at most `max_block_age` old.
+    /// Uses the committed block timestamp, so no RPC access is needed.
+    pub fn with_max_block_age(mut self, max_block_age: Duration) -> Self {
+        self.max_block_age = Some(max_block_age);
+        self
+    }
+
+    /// Only accept proofs whose billing period may end at most `max_grace_period`
+    /// before the block the payment was checked against. The grace period is chosen
+    /// by the prover, so it is capped at [`DEFAULT_MAX_GRACE_PERIOD`] by default.
+    pub fn with_max_grace_period(mut self, max_grace_period: Duration) -> Self {
+        self.max_grace_period = max_grace_period;
+        self
+    }
+
+    /// Whether proofs signed with the fixed legacy message are accepted
+    pub fn allows_legacy_signatures(&self) -> bool {
+        self.allow_legacy_signatures
+    }
+
+    /// Image IDs this verifier accepts
+    pub fn accepted_image_ids(&self) -> &[Digest] {
+        &self.accepted_image_ids
+    }
+
+    /// Verify a `fkp1_` proof token
+    pub fn verify_token(&self, token: &str) -> Result<VerifiedPayment, VerificationError> {
+        let token = ProofToken::decode(token)?;
+        self.verify(token.image_id, &token.seal, &token.journal)
+    }
+
+    /// Verify an ABI-encoded seal and journal produced by a specific image ID
+    pub fn verify(
+        &self,
+        image_id: Digest,
+        seal: &[u8],
+        journal: &[u8],
+    ) -> Result<VerifiedPayment, VerificationError> {
+        if !self.accepted_image_ids.contains(&image_id) {
+            return Err(VerificationError::UnknownImageId(image_id));
+        }
+
+        // Decode the ABI-encoded seal into a receipt bound to the image ID and the journal
+        let receipt = match decode_seal(Bytes::copy_from_slice(seal), image_id, journal.to_vec()) {
+            Ok(Receipt::Base(receipt)) => receipt,
+            Ok(Receipt::SetInclusion(_)) => return Err(VerificationError::UnsupportedSealType),
+            Err(e) => return Err(VerificationError::InvalidSeal(e.to_string())),
+        };
+        receipt
+            .verify(image_id)
+            .map_err(|e| VerificationError::InvalidReceipt(e.to_string()))?;
+
+        self.check_journal(image_id, journal)
+    }
+
+    /// Check the public outputs of a proof whose receipt was verified
+    fn check_journal(
+        &self,
+        image_id: Digest,
+        journal: &[u8],
+    ) -> Result<VerifiedPayment, VerificationError> {
+        let decoded = Journal::abi_decode(journal)
+            .map_err(|e| VerificationError::InvalidJournal(e.to_string()))?;
+
+        if !self.accept_any_receiver
+            && !self
+                .accepted_receivers
+                .contains(&(decoded.chainId, decoded.paymentContract))
+        {
+            return Err(VerificationError::WrongPaymentReceiver {
+                payment_contract: decoded.paymentContract,
+                chain_id: decoded.chainId,
+            });
+        }
+
+        let period = BillingPeriod::from_onchain(decoded.month)
+            .map_err(|e| VerificationError::InvalidJournal(e.to_string()))?;
+
+        if decoded.legacySignature {
+            if !self.allow_legacy_signatures {
+                return Err(VerificationError::LegacySignature);
+            }
+        } else if decoded.expiry < unix_timestamp() {
+            return Err(VerificationError::AuthorizationExpired(decoded.expiry));
+        }
+
+        let grace_period = Duration::from_secs(decoded.gracePeriod);
+        if grace_period > self.max_grace_period {
+            return Err(VerificationError::GracePeriodTooLong {
+                found: grace_period,
+                max: self.max_grace_period,
+            });
+        }
+        if let Some(max_age) = self.max_block_age {
+            let age = Duration::from_secs(unix_timestamp().saturating_sub(decoded.blockTimestamp));
+            if age > max_age {
+                return Err(VerificationError::StaleProof { age, max_age });
+            }
+        }
+
+        Ok(VerifiedPayment {
+            image_id,
+            commitment: decoded.commitment,
+            payment_contract: decoded.paymentContract,
+            chain_id: decoded.chainId,
+            period,
+            challenge: decoded.challenge,
+            nullifier: decoded.nullifier,
+            epoch: decoded.epoch,
+            epoch_length: Duration::from_secs(decoded.epochLength),
+            expiry: decoded.expiry,
+            legacy_signature: decoded.legacySignature,
+            block_timestamp: decoded.blockTimestamp,
+            grace_period,
+        })
+    }
+
+    /// Verify an ABI-encoded seal and journal pair without a known image ID,
+    /// trying each accepted image ID in turn
+    pub fn verify_seal(
+        &self,
+        seal: &[u8],
+        journal: &[u8],
+    ) -> Result<VerifiedPayment, VerificationError> {
+        let mut last_error = VerificationError::InvalidReceipt("no accepted image IDs".into());
+        for image_id in &self.accepted_image_ids {
+            match self.verify(*image_id, seal, journal) {
+                Ok(payment) => return Ok(payment),
+                Err(e) => last_error = e,
+            }
+        }
+        Err(last_error)
+    }
+}
+
+/// Verify a `fkp1_` proof token against the accepted guest image IDs and the
+/// service's PaymentReceiver contract
+pub fn verify_proof_token(
+    token: &str,
+    accepted_image_ids: &[Digest],
+    chain_id: u64,
+    payment_contract: Address,
+) -> Result<VerifiedPayment, VerificationError> {
+    Verifier::new(accepted_image_ids.iter().copied())
+        .with_payment_receiver(chain_id, payment_contract)
+        .verify_token(token)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_primitives::address;
+
+    use super::*;
+
+    const RECEIVER: Address = address!("6fEDEb0B4942A8b438AFE68ba7c8Af4637c41903");
+    const CHAIN_ID: u64 = 421614;
+    const IMAGE_ID: Digest = Digest::new([1; 8]);
+
+    fn verifier() -> Verifier {
+        Verifier::new([IMAGE_ID]).with_payment_receiver(CHAIN_ID, RECEIVER)
+    }
+
+    /// Journal of a fresh proof for July 2025 that every check accepts
+    fn journal() -> Journal {
+        let now = unix_timestamp();
+        Journal {
+            commitment: Commitment::new(0, 100, B256::repeat_byte(0xbb), B256::ZERO),
+            paymentContract: RECEIVER,
+            chainId: CHAIN_ID,
+            month: 666,
+            challenge: B256::repeat_byte(0xcc),
+            nullifier: B256::repeat_byte(0xdd),
+            epoch: (now - 60) / 86400,
+            epochLength: 86400,
+            expiry: now + 600,
+            legacySignature: false,
+            blockTimestamp: now - 60,
+            gracePeriod: 3 * 86400,
+        }
+    }
+
+    fn check(verifier: &Verifier, journal: Journal) -> Result<VerifiedPayment, VerificationError> {
+        verifier.check_journal(IMAGE_ID, &journal.abi_encode())
+    }
+
+    fn reason(result: Result<VerifiedPayment, VerificationError>) -> &'static str {
+        result.unwrap_err().reason()
+    }
+
+    #[test]
+    fn decodes_accepted_journal() {
+        let journal = journal();
+        let payment = check(&verifier(), journal.clone()).unwrap();
+
+        assert_eq!(payment.image_id, IMAGE_ID);
+        assert_eq!(payment.payment_contract, RECEIVER);
+        assert_eq!(payment.chain_id, CHAIN_ID);
+        assert_eq!(payment.period, BillingPeriod::new(2025, 7).unwrap());
+        assert_eq!(payment.nullifier, journal.nullifier);
+        assert_eq!(payment.epoch, journal.epoch);
+        assert_eq!(payment.epoch_length, Duration::from_secs(86400));
+        assert_eq!(payment.block_timestamp, journal.blockTimestamp);
+        assert_eq!(payment.grace_period, Duration::from_secs(3 * 86400));
+        assert_eq!(payment.commitment_id(), (U256::from(100), 0));
+        assert_eq!(payment.block_digest(), B256::repeat_byte(0xbb));
+
+        payment.check_challenge(journal.challenge).unwrap();
+        assert_eq!(
+            payment.check_challenge(B256::ZERO).unwrap_err().reason(),
+            "CHALLENGE_MISMATCH"
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_image_ids_and_tokens() {
+        let other = Digest::new([2; 8]);
+        assert_eq!(
+            reason(verifier().verify(other, &[], &journal().abi_encode())),
+            "UNKNOWN_IMAGE_ID"
+        );
+        assert_eq!(
+            reason(verifier().verify_token("fkp1_invalid")),
+            "INVALID_PROOF_TOKEN"
+        );
+        let token = ProofToken::new(other, vec![1, 2, 3], journal().abi_encode()).encode();
+        assert_eq!(reason(verifier().verify_token(&token)), "UNKNOWN_IMAGE_ID");
+        assert_eq!(
+            reason(Verifier::new([IMAGE_ID]).verify(IMAGE_ID, &[0; 4], &[])),
+            "INVALID_SEAL"
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_journals() {
+        assert_eq!(
+            reason(verifier().check_journal(IMAGE_ID, &[1, 2, 3])),
+            "INVALID_JOURNAL"
+        );
+        let journal = Journal {
+            month: u64::MAX,
+            ..journal()
+        };
+        assert_eq!(reason(check(&verifier(), journal)), "INVALID_JOURNAL");
+    }
+
+    #[test]
+    fn only_accepts_configured_receivers() {
+        let other = Journal {
+            paymentContract: Address::repeat_byte(0x11),
+            ..journal()
+        };
+        assert_eq!(
+            reason(check(&verifier(), other.clone())),
+            "WRONG_PAYMENT_RECEIVER"
+        );
+
+        let other_chain = Journal {
+            chainId: 1,
+            ..journal()
+        };
+        assert_eq!(
+            reason(check(&verifier(), other_chain)),
+            "WRONG_PAYMENT_RECEIVER"
+        );
+
+        // Without receivers nothing is accepted, unless any receiver is allowed explicitly
+        let without_receivers = Verifier::new([IMAGE_ID]);
+        assert_eq!(
+            reason(check(&without_receivers, journal())),
+            "WRONG_PAYMENT_RECEIVER"
+        );
+        check(&without_receivers.accept_any_payment_receiver(), other).unwrap();
+    }
+
+    #[test]
+    fn checks_authorization_expiry_and_legacy_signatures() {
+        let expired = Journal {
+            expiry: unix_timestamp() - 1,
+            ..journal()
+        };
+        assert_eq!(reason(check(&verifier(), expired)), "AUTHORIZATION_EXPIRED");
+
+        let legacy = Journal {
+            expiry: 0,
+            legacySignature: true,
+            ..journal()
+        };
+        assert_eq!(
+            reason(check(&verifier(), legacy.clone())),
+            "LEGACY_SIGNATURE"
+        );
+        let verifier = verifier().allow_legacy_signatures(true);
+        assert!(verifier.allows_legacy_signatures());
+        assert!(check(&verifier, legacy).unwrap().legacy_signature);
+    }
+
+    #[test]
+    fn caps_the_grace_period() {
+        let long_grace = Journal {
+            gracePeriod: DEFAULT_MAX_GRACE_PERIOD.as_secs() + 1,
+            ..journal()
+        };
+        assert_eq!(
+            reason(check(&verifier(), long_grace.clone())),
+            "GRACE_PERIOD_TOO_LONG"
+        );
+
+        let huge_grace = Journal {
+            gracePeriod: u64::MAX,
+            ..journal()
+        };
+        assert_eq!(
+            reason(check(&verifier(), huge_grace)),
+            "GRACE_PERIOD_TOO_LONG"
+        );
+
+        let verifier = verifier().with_max_grace_period(Duration::from_secs(7 * 86400));
+        check(&verifier, long_grace).unwrap();
+    }
+
+    #[test]
+    fn rejects_stale_blocks() {
+        let stale = Journal {
+            blockTimestamp: unix_timestamp() - 3600,
+            ..journal()
+        };
+        // Block age is only checked when configured
+        check(&verifier(), stale.clone()).unwrap();
+
+        let verifier = verifier().with_max_block_age(Duration::from_secs(600));
+        check(&verifier, journal()).unwrap();
+        assert_eq!(reason(check(&verifier, stale)), "STALE_PROOF");
+    }
+}